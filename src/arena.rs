@@ -0,0 +1,285 @@
+use std::fs;
+use std::path::Path;
+
+use bevy::asset::{AssetLoader, LoadContext, LoadedAsset};
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use bevy::utils::BoxedFuture;
+use serde::{Deserialize, Serialize};
+
+use crate::obstacles::ObstacleDef;
+use crate::rules::{MatchRules, RonFileError, TeamLayout};
+use crate::{load_indexed, ArenaAssets, PlayerId};
+
+/// The description of an arena, loaded from the `.arena` files of the assets.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TypeUuid)]
+#[uuid = "2ed32e4f-f9ac-40e7-b7d9-d09ad9205f6d"]
+#[serde(default)]
+pub struct Arena {
+    pub name: String,
+    /// Replaces the background color of the theme when defined.
+    pub background: Option<Color>,
+    pub edges: Vec<ArenaRect>,
+    pub field_lines: Vec<ArenaRect>,
+    /// One entry per player of the match, the first one is the human player.
+    pub players: Vec<ArenaPlayer>,
+    /// How far from its start position a paddle can go.
+    pub paddle_range: f32,
+    /// The half extents of the area in which the bonuses spawn.
+    pub bonus_area: Vec2,
+    pub obstacles: Vec<ObstacleDef>,
+}
+
+/// The part of the arena that belongs to one player.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ArenaPlayer {
+    pub goal: ArenaRect,
+    /// The part of the field the player defends.
+    pub side: ArenaRect,
+    pub paddle_start: Vec2,
+    /// The rotation of the paddle, a quarter turn for the paddles at the top or the bottom.
+    #[serde(default)]
+    pub paddle_rotation: f32,
+    /// Where the lifebar, the won rounds and the health of the player are displayed.
+    pub hud_position: Vec2,
+}
+
+/// The segment a paddle moves along.
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct PaddleTrack {
+    pub start: Vec2,
+    pub axis: Vec2,
+    pub rotation: f32,
+    /// How far from its start position the paddle can go.
+    pub range: f32,
+}
+
+impl PaddleTrack {
+    /// Keeps a position on the track.
+    pub fn clamp(&self, position: Vec2) -> Vec2 {
+        let offset = (position - self.start).dot(self.axis).clamp(-self.range, self.range);
+        self.start + self.axis * offset
+    }
+}
+
+/// A rectangle of the arena, its position is the one of its center.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ArenaRect {
+    pub position: Vec2,
+    pub size: Vec2,
+    /// The rotation around the center, in radians.
+    #[serde(default)]
+    pub rotation: f32,
+}
+
+impl ArenaRect {
+    pub fn new(x: f32, y: f32, width: f32, height: f32) -> ArenaRect {
+        ArenaRect { position: Vec2::new(x, y), size: Vec2::new(width, height), rotation: 0. }
+    }
+
+    pub fn transform(&self) -> Transform {
+        Transform {
+            translation: self.position.extend(0.),
+            rotation: Quat::from_rotation_z(self.rotation),
+            ..Default::default()
+        }
+    }
+
+    pub fn half_extends(&self) -> Vec3 {
+        (self.size / 2.).extend(0.)
+    }
+
+    pub fn contains(&self, point: Vec2) -> bool {
+        self.local_position(point).abs().cmple(self.size / 2.).all()
+    }
+
+    /// How far a point is from the rectangle, zero when inside.
+    pub fn distance(&self, point: Vec2) -> f32 {
+        (self.local_position(point).abs() - self.size / 2.).max(Vec2::ZERO).length()
+    }
+
+    /// The position of a point relative to the center and rotation of the rectangle.
+    pub fn local_position(&self, point: Vec2) -> Vec2 {
+        Quat::from_rotation_z(-self.rotation)
+            .mul_vec3((point - self.position).extend(0.))
+            .truncate()
+    }
+}
+
+impl Default for Arena {
+    fn default() -> Arena {
+        Arena {
+            name: String::from("Classic"),
+            background: None,
+            edges: vec![ArenaRect::new(0., 7., 28., 2.), ArenaRect::new(0., -7., 28., 2.)],
+            field_lines: vec![
+                ArenaRect::new(0., 6., 22.5, 0.1),
+                ArenaRect::new(0., -6., 22.5, 0.1),
+            ],
+            players: vec![
+                ArenaPlayer {
+                    goal: ArenaRect::new(13., 0., 2., 20.),
+                    side: ArenaRect::new(6., 0., 12., 12.),
+                    paddle_start: Vec2::new(11., 0.),
+                    paddle_rotation: 0.,
+                    hud_position: Vec2::new(7.5, 6.625),
+                },
+                ArenaPlayer {
+                    goal: ArenaRect::new(-13., 0., 2., 20.),
+                    side: ArenaRect::new(-6., 0., 12., 12.),
+                    paddle_start: Vec2::new(-11., 0.),
+                    paddle_rotation: 0.,
+                    hud_position: Vec2::new(-7.5, 6.625),
+                },
+            ],
+            paddle_range: 8.,
+            bonus_area: Vec2::new(10., 5.5),
+            obstacles: Vec::new(),
+        }
+    }
+}
+
+impl Arena {
+    pub fn player(&self, player: PlayerId) -> &ArenaPlayer {
+        &self.players[player.0]
+    }
+
+    pub fn player_ids(&self) -> impl Iterator<Item = PlayerId> {
+        (0..self.players.len()).map(PlayerId)
+    }
+
+    /// Whether a circle stands clear of the edges, the goals and the obstacles.
+    pub fn is_clear(&self, position: Vec2, radius: f32) -> bool {
+        let rects = self.edges.iter().chain(self.players.iter().map(|player| &player.goal));
+        rects.map(|rect| rect.distance(position)).all(|distance| distance >= radius)
+            && self.obstacles.iter().all(|obstacle| obstacle.distance(position) >= radius)
+    }
+
+    pub fn paddle_start(&self, player: PlayerId) -> Vec2 {
+        self.player(player).paddle_start
+    }
+
+    /// The direction a paddle moves along.
+    pub fn paddle_axis(&self, player: PlayerId) -> Vec2 {
+        Vec2::new(
+            -self.player(player).paddle_rotation.sin(),
+            self.player(player).paddle_rotation.cos(),
+        )
+    }
+
+    /// The tracks of the paddles of a player, one per member of its team.
+    pub fn paddle_tracks(&self, player: PlayerId, layout: TeamLayout) -> Vec<PaddleTrack> {
+        let start = self.paddle_start(player);
+        let axis = self.paddle_axis(player);
+        let track = PaddleTrack {
+            start,
+            axis,
+            rotation: self.player(player).paddle_rotation,
+            range: self.paddle_range,
+        };
+
+        match layout {
+            TeamLayout::Single => vec![track],
+            TeamLayout::FrontBack => {
+                // The front paddle stands closer to the center of the arena
+                let inward = if axis.perp().dot(-start) > 0. { axis.perp() } else { -axis.perp() };
+                vec![track, PaddleTrack { start: start + inward * 3., ..track }]
+            }
+            TeamLayout::TopBottom => {
+                let range = self.paddle_range / 2.;
+                vec![
+                    PaddleTrack { start: start + axis * range, range, ..track },
+                    PaddleTrack { start: start - axis * range, range, ..track },
+                ]
+            }
+        }
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), RonFileError> {
+        let content = ron::ser::to_string_pretty(self, Default::default())?;
+        Ok(fs::write(path, content)?)
+    }
+}
+
+#[derive(Default)]
+pub struct ArenaLoader;
+
+impl AssetLoader for ArenaLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            load_context.set_default_asset(LoadedAsset::new(parse_arena(bytes)?));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["arena"]
+    }
+}
+
+fn parse_arena(bytes: &[u8]) -> Result<Arena, anyhow::Error> {
+    let arena: Arena = ron::de::from_bytes(bytes)?;
+    if arena.players.len() < 2 {
+        anyhow::bail!("the {} arena needs at least two players", arena.name);
+    }
+    Ok(arena)
+}
+
+/// The arenas listed in an `.arenas` index file, in the order they are selected.
+#[derive(Debug, TypeUuid)]
+#[uuid = "8f0c1f0e-5a2b-4d8e-9c4a-3b7e6d1f2a90"]
+pub struct ArenaIndex(pub Vec<Handle<Arena>>);
+
+#[derive(Default)]
+pub struct ArenaIndexLoader;
+
+impl AssetLoader for ArenaIndexLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let arenas = load_indexed(bytes, load_context, parse_arena).await?;
+            load_context.set_default_asset(LoadedAsset::new(ArenaIndex(arenas)));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["arenas"]
+    }
+}
+
+/// The names of the arenas that can be selected for a match.
+pub fn arena_names(assets: &ArenaAssets, arenas: &Assets<Arena>) -> Vec<String> {
+    assets.all().iter().filter_map(|h| arenas.get(h)).map(|a| a.name.clone()).collect()
+}
+
+pub fn find_arena<'a>(
+    assets: &ArenaAssets,
+    arenas: &'a Assets<Arena>,
+    name: &str,
+) -> Option<&'a Arena> {
+    assets.all().iter().filter_map(|h| arenas.get(h)).find(|a| a.name == name)
+}
+
+/// Copies the arena selected by the rules into the `Arena` resource.
+pub fn load_selected_arena(
+    rules: Res<MatchRules>,
+    assets: Res<ArenaAssets>,
+    arenas: Res<Assets<Arena>>,
+    mut arena: ResMut<Arena>,
+) {
+    *arena = match find_arena(&assets, &arenas, &rules.arena) {
+        Some(selected) => selected.clone(),
+        None => {
+            warn!("Could not find the {} arena, using the default one", rules.arena);
+            Arena::default()
+        }
+    };
+}
@@ -1,8 +1,15 @@
+use std::path::Path;
+
+use anyhow::Context;
 use benimator::SpriteSheetAnimation;
+use bevy::asset::{Asset, LoadContext, LoadedAsset};
 use bevy::prelude::*;
 use bevy_asset_loader::AssetCollection;
 use bevy_kira_audio::AudioSource;
 
+use crate::arena::{Arena, ArenaIndex};
+use crate::breakout::{BreakoutLevel, LevelIndex};
+
 #[derive(AssetCollection)]
 pub struct BonusesAssets {
     #[asset(texture_atlas(tile_size_x = 64., tile_size_y = 64., columns = 4, rows = 5))]
@@ -12,6 +19,42 @@ pub struct BonusesAssets {
     #[asset(texture_atlas(tile_size_x = 64., tile_size_y = 64., columns = 2, rows = 1))]
     #[asset(path = "images/shrink_increase_paddle.png")]
     pub paddle_texture_atlas: Handle<TextureAtlas>,
+
+    #[asset(texture_atlas(tile_size_x = 64., tile_size_y = 64., columns = 1, rows = 1))]
+    #[asset(path = "images/shield.png")]
+    pub shield_texture_atlas: Handle<TextureAtlas>,
+
+    #[asset(texture_atlas(tile_size_x = 64., tile_size_y = 64., columns = 1, rows = 1))]
+    #[asset(path = "images/magnet.png")]
+    pub magnet_texture_atlas: Handle<TextureAtlas>,
+
+    #[asset(texture_atlas(tile_size_x = 64., tile_size_y = 64., columns = 1, rows = 1))]
+    #[asset(path = "images/ghost.png")]
+    pub ghost_texture_atlas: Handle<TextureAtlas>,
+
+    #[asset(texture_atlas(tile_size_x = 64., tile_size_y = 64., columns = 4, rows = 1))]
+    #[asset(path = "images/ball_bonuses.png")]
+    pub ball_texture_atlas: Handle<TextureAtlas>,
+
+    #[asset(texture_atlas(tile_size_x = 64., tile_size_y = 64., columns = 1, rows = 1))]
+    #[asset(path = "images/reverse.png")]
+    pub reverse_texture_atlas: Handle<TextureAtlas>,
+
+    #[asset(texture_atlas(tile_size_x = 64., tile_size_y = 64., columns = 2, rows = 1))]
+    #[asset(path = "images/time_bonuses.png")]
+    pub time_texture_atlas: Handle<TextureAtlas>,
+
+    #[asset(texture_atlas(tile_size_x = 64., tile_size_y = 64., columns = 2, rows = 1))]
+    #[asset(path = "images/portals.png")]
+    pub portal_texture_atlas: Handle<TextureAtlas>,
+
+    #[asset(texture_atlas(tile_size_x = 64., tile_size_y = 64., columns = 2, rows = 1))]
+    #[asset(path = "images/health_bonuses.png")]
+    pub health_texture_atlas: Handle<TextureAtlas>,
+
+    #[asset(texture_atlas(tile_size_x = 64., tile_size_y = 64., columns = 1, rows = 1))]
+    #[asset(path = "images/bonus_warning.png")]
+    pub warning_texture_atlas: Handle<TextureAtlas>,
 }
 
 #[derive(AssetCollection)]
@@ -36,6 +79,13 @@ pub struct SpacebarAssets {
     pub loop_animation: Handle<SpriteSheetAnimation>,
 }
 
+#[derive(AssetCollection)]
+pub struct ObstacleAssets {
+    #[asset(texture_atlas(tile_size_x = 64., tile_size_y = 64., columns = 1, rows = 1))]
+    #[asset(path = "images/bumper.png")]
+    pub bumper_texture_atlas: Handle<TextureAtlas>,
+}
+
 #[derive(AssetCollection)]
 pub struct HudAssets {
     #[asset(texture_atlas(tile_size_x = 64., tile_size_y = 64., columns = 4, rows = 1))]
@@ -70,6 +120,97 @@ pub struct AudioAssets {
     #[asset(path = "sfx/hit_1.wav")]
     pub hit_1: Handle<AudioSource>,
 
+    #[asset(path = "sfx/shield_break.wav")]
+    pub shield_break: Handle<AudioSource>,
+
+    #[asset(path = "sfx/portal.wav")]
+    pub portal: Handle<AudioSource>,
+
     #[asset(path = "audiotracks/bevyjam.wav")]
     pub track: Handle<AudioSource>,
 }
+
+#[derive(AssetCollection)]
+pub struct FontAssets {
+    #[asset(path = "fonts/monogram.ttf")]
+    pub monogram: Handle<Font>,
+}
+
+/// The file listing the arenas that can be selected, adding an arena only takes a line there.
+const ARENA_INDEX: &str = "arenas/index.arenas";
+/// The arena saved by the editor, it is not in the index for the editor to find it.
+const CUSTOM_ARENA: &str = "arenas/custom.arena";
+/// The file listing the levels of the breakout.
+const LEVEL_INDEX: &str = "levels/index.levels";
+
+/// The arenas of the index, followed by the one saved by the editor.
+pub struct ArenaAssets {
+    arenas: Vec<Handle<Arena>>,
+    /// The arena saved by the editor.
+    pub custom: Handle<Arena>,
+}
+
+impl ArenaAssets {
+    pub fn all(&self) -> &[Handle<Arena>] {
+        &self.arenas
+    }
+}
+
+impl AssetCollection for ArenaAssets {
+    fn create(world: &mut World) -> Self {
+        let cell = world.cell();
+        let asset_server = cell.get_resource::<AssetServer>().expect("the asset server");
+        let indexes = cell.get_resource::<Assets<ArenaIndex>>().expect("the arena indexes");
+        let custom = asset_server.get_handle(CUSTOM_ARENA);
+        let mut arenas = indexes.get(ARENA_INDEX).map(|index| index.0.clone()).unwrap_or_default();
+        arenas.push(custom.clone());
+        ArenaAssets { arenas, custom }
+    }
+
+    fn load(world: &mut World) -> Vec<HandleUntyped> {
+        let asset_server = world.get_resource::<AssetServer>().expect("the asset server");
+        vec![asset_server.load_untyped(ARENA_INDEX), asset_server.load_untyped(CUSTOM_ARENA)]
+    }
+}
+
+/// The levels of the index.
+pub struct LevelAssets {
+    levels: Vec<Handle<BreakoutLevel>>,
+}
+
+impl LevelAssets {
+    pub fn all(&self) -> &[Handle<BreakoutLevel>] {
+        &self.levels
+    }
+}
+
+impl AssetCollection for LevelAssets {
+    fn create(world: &mut World) -> Self {
+        let indexes = world.get_resource::<Assets<LevelIndex>>().expect("the level indexes");
+        let levels = indexes.get(LEVEL_INDEX).map(|index| index.0.clone()).unwrap_or_default();
+        LevelAssets { levels }
+    }
+
+    fn load(world: &mut World) -> Vec<HandleUntyped> {
+        let asset_server = world.get_resource::<AssetServer>().expect("the asset server");
+        vec![asset_server.load_untyped(LEVEL_INDEX)]
+    }
+}
+
+/// Loads the files listed by an index along with it, as labeled assets of the index,
+/// so that the loading state waits for all of them.
+pub async fn load_indexed<T: Asset>(
+    bytes: &[u8],
+    load_context: &mut LoadContext<'_>,
+    parse: fn(&[u8]) -> Result<T, anyhow::Error>,
+) -> Result<Vec<Handle<T>>, anyhow::Error> {
+    let files: Vec<String> = ron::de::from_bytes(bytes)?;
+    let folder = load_context.path().parent().map(Path::to_path_buf).unwrap_or_default();
+    let mut handles = Vec::with_capacity(files.len());
+    for file in files {
+        let bytes = load_context.read_asset_bytes(folder.join(&file)).await?;
+        let asset = parse(&bytes).with_context(|| format!("could not load {}", file))?;
+        handles.push(load_context.set_labeled_asset(&file, LoadedAsset::new(asset)));
+    }
+    Ok(handles)
+}
@@ -0,0 +1,49 @@
+use bevy::prelude::*;
+use heron::prelude::*;
+
+use crate::{Ball, Bonus, TakenBonusEvent, BALL_RADIUS};
+
+pub fn manage_ball_scale_bonuses(
+    mut taken_bonus_reader: EventReader<TakenBonusEvent>,
+    mut ball_scale: ResMut<BallScale>,
+) {
+    for TakenBonusEvent { bonus, .. } in taken_bonus_reader.iter() {
+        if let Bonus::BallScale { size, mass } = bonus {
+            ball_scale.size = (ball_scale.size * size).clamp(0.5, 2.25);
+            ball_scale.mass = (ball_scale.mass * mass).clamp(0.25, 4.);
+        }
+    }
+}
+
+/// Gives every ball the size of the current scale.
+pub fn scale_balls(
+    ball_scale: Res<BallScale>,
+    mut balls_query: Query<(&mut CollisionShape, &mut TextureAtlasSprite), With<Ball>>,
+) {
+    let radius = BALL_RADIUS * ball_scale.size;
+    for (mut shape, mut sprite) in balls_query.iter_mut() {
+        // Only touch the shapes that changed, heron rebuilds the colliders of the changed ones
+        if !matches!(*shape, CollisionShape::Sphere { radius: r } if (r - radius).abs() < 0.001) {
+            *shape = CollisionShape::Sphere { radius };
+            sprite.custom_size = Some(Vec2::splat(radius * 2.));
+        }
+    }
+}
+
+pub fn reset_ball_scale(mut ball_scale: ResMut<BallScale>) {
+    *ball_scale = BallScale::default();
+}
+
+/// The size and the mass of the balls of the round, relative to the default ones.
+/// The paddles speed the heavy balls up less than the light ones.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BallScale {
+    pub size: f32,
+    pub mass: f32,
+}
+
+impl Default for BallScale {
+    fn default() -> BallScale {
+        BallScale { size: 1., mass: 1. }
+    }
+}
@@ -0,0 +1,73 @@
+use bevy::prelude::*;
+use heron::prelude::*;
+
+use crate::theme::Theme;
+use crate::{Bonus, Paddle, PlayerId, TakenBonusEvent};
+
+/// The seconds the paddles of the opponents stay frozen.
+const FREEZE_DURATION: f32 = 2.5;
+const FROZEN_PADDLE_COLOR: Color = Color::rgb(0.6, 0.85, 1.);
+
+#[allow(clippy::type_complexity)]
+pub fn manage_freeze_paddles_bonus(
+    mut commands: Commands,
+    mut taken_bonus_reader: EventReader<TakenBonusEvent>,
+    mut paddles_query: Query<
+        (Entity, &PlayerId, &mut Velocity, &mut Sprite, Option<&mut Frozen>),
+        With<Paddle>,
+    >,
+) {
+    for TakenBonusEvent { bonus, .. } in taken_bonus_reader.iter() {
+        if let Bonus::FreezePaddles { benefiting_player } = bonus {
+            // Every opponent of the benefiting player stops where it stands,
+            // the paddles are kinematic bodies so stopping them is only clearing their velocity
+            for (entity, player, mut velocity, mut sprite, frozen) in paddles_query.iter_mut() {
+                if benefiting_player == player {
+                    continue;
+                }
+
+                *velocity = Default::default();
+                sprite.color = FROZEN_PADDLE_COLOR;
+                match frozen {
+                    Some(mut frozen) => frozen.timer.reset(),
+                    None => {
+                        let timer = Timer::from_seconds(FREEZE_DURATION, false);
+                        commands.entity(entity).insert(Frozen { timer });
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Lets the frozen paddles move again, with their own color back.
+pub fn thaw_frozen_paddles(
+    mut commands: Commands,
+    time: Res<Time>,
+    theme: Res<Theme>,
+    mut paddles_query: Query<(Entity, &PlayerId, &mut Sprite, &mut Frozen)>,
+) {
+    for (entity, player, mut sprite, mut frozen) in paddles_query.iter_mut() {
+        if frozen.timer.tick(time.delta()).finished() {
+            sprite.color = theme.paddle_color(*player);
+            commands.entity(entity).remove::<Frozen>();
+        }
+    }
+}
+
+pub fn reset_frozen_paddles(
+    mut commands: Commands,
+    theme: Res<Theme>,
+    mut paddles_query: Query<(Entity, &PlayerId, &mut Sprite), With<Frozen>>,
+) {
+    for (entity, player, mut sprite) in paddles_query.iter_mut() {
+        sprite.color = theme.paddle_color(*player);
+        commands.entity(entity).remove::<Frozen>();
+    }
+}
+
+/// A paddle that cannot move for a moment.
+#[derive(Component)]
+pub struct Frozen {
+    timer: Timer,
+}
@@ -0,0 +1,115 @@
+use std::f32::consts::PI;
+
+use bevy::prelude::*;
+use heron::prelude::*;
+use rand::Rng;
+
+use crate::{Ball, Bonus, Paddle, PlayerId, TakenBonusEvent};
+
+/// The seconds the balls stay ghosts.
+const GHOST_DURATION: f32 = 12.;
+/// How close to a paddle a ghost ball shows up again.
+const GHOST_REVEAL_DISTANCE: f32 = 4.;
+/// How far off the computers guess where a hidden ghost ball is.
+const GHOST_AI_ERROR: f32 = 1.5;
+
+pub fn manage_ghost_ball_bonus(
+    mut commands: Commands,
+    mut taken_bonus_reader: EventReader<TakenBonusEvent>,
+    balls_query: Query<(Entity, &GlobalTransform, &Velocity), With<Ball>>,
+) {
+    for TakenBonusEvent { bonus, .. } in taken_bonus_reader.iter() {
+        if let Bonus::GhostBall { benefiting_player } = bonus {
+            for (entity, transform, velocity) in balls_query.iter() {
+                commands.entity(entity).insert(Ghost {
+                    owner: *benefiting_player,
+                    timer: Timer::from_seconds(GHOST_DURATION, false),
+                    alpha: 1.,
+                    hidden_for: 0.,
+                    last_seen: transform.translation.truncate(),
+                    last_velocity: velocity.linear.truncate(),
+                    guess_error: Vec2::ZERO,
+                });
+            }
+        }
+    }
+}
+
+/// The ghost balls vanish in the sides of the opponents of their owner
+/// until they come close to a paddle, they flicker now and then while hidden.
+pub fn fade_ghost_balls(
+    mut commands: Commands,
+    time: Res<Time>,
+    paddles_query: Query<(&GlobalTransform, &PlayerId), With<Paddle>>,
+    mut balls_query: Query<(Entity, &Ball, &GlobalTransform, &Velocity, &mut Ghost)>,
+) {
+    let mut rng = rand::thread_rng();
+
+    for (entity, ball, transform, velocity, mut ghost) in balls_query.iter_mut() {
+        if ghost.timer.tick(time.delta()).finished() {
+            commands.entity(entity).remove::<Ghost>();
+            continue;
+        }
+
+        let position = transform.translation.truncate();
+        let hidden = match ball.current_side {
+            Some(side) if side != ghost.owner => paddles_query
+                .iter()
+                .filter(|(_, player)| **player == side)
+                .all(|(t, _)| t.translation.truncate().distance(position) > GHOST_REVEAL_DISTANCE),
+            _ => false,
+        };
+
+        if hidden {
+            // The computers keep the last place they saw the ball, with some error
+            if ghost.hidden_for == 0. {
+                let angle = rng.gen_range(0.0..2. * PI);
+                ghost.guess_error = Vec2::new(angle.cos(), angle.sin()) * GHOST_AI_ERROR;
+            }
+            ghost.hidden_for += time.delta_seconds();
+        } else {
+            ghost.hidden_for = 0.;
+            ghost.last_seen = position;
+            ghost.last_velocity = velocity.linear.truncate();
+        }
+
+        let target = if hidden { 0. } else { 1. };
+        let step = 4. * time.delta_seconds();
+        ghost.alpha = if ghost.alpha < target {
+            (ghost.alpha + step).min(target)
+        } else {
+            (ghost.alpha - step).max(target)
+        };
+
+        // A short flash every few moments gives a hint of where the hidden ball is
+        let flash = hidden && (ghost.hidden_for % 0.75) < 0.08;
+        if flash {
+            ghost.alpha = ghost.alpha.max(0.35);
+        }
+    }
+}
+
+/// A ball hidden to the opponents of its owner while it crosses their side.
+#[derive(Component)]
+pub struct Ghost {
+    owner: PlayerId,
+    timer: Timer,
+    /// How visible the ball is, from 0 to 1.
+    pub alpha: f32,
+    hidden_for: f32,
+    last_seen: Vec2,
+    last_velocity: Vec2,
+    guess_error: Vec2,
+}
+
+impl Ghost {
+    /// Where the computers think the ball is: its position when visible,
+    /// otherwise a guess from where it vanished, off by some error.
+    pub fn apparent_position(&self, position: Vec2) -> Vec2 {
+        if self.hidden_for > 0. {
+            self.last_seen + self.last_velocity * self.hidden_for + self.guess_error
+        } else {
+            position
+        }
+    }
+}
@@ -0,0 +1,110 @@
+use std::f32::consts::PI;
+
+use bevy::prelude::*;
+
+use crate::rules::MatchRules;
+use crate::theme::Theme;
+use crate::{Bonus, GameScore, Lifebar, PlayerId, TakenBonusEvent};
+
+/// The seconds the lifebars glow when they gain health.
+const LIFEBAR_GAIN_DURATION: f32 = 0.8;
+
+/// Gives health back with the extra life bonus, or takes it from the opponents with the steal.
+/// A side with no health left is already out of the round, it cannot be healed back in
+/// before the round ends.
+pub fn manage_health_bonuses(
+    mut commands: Commands,
+    mut score: ResMut<GameScore>,
+    rules: Res<MatchRules>,
+    mut taken_bonus_reader: EventReader<TakenBonusEvent>,
+    lifebars_query: Query<(Entity, &PlayerId), With<Lifebar>>,
+) {
+    for TakenBonusEvent { bonus, .. } in taken_bonus_reader.iter() {
+        let (benefiting_player, stolen) = match bonus {
+            Bonus::ExtraLife { benefiting_player } => (*benefiting_player, false),
+            Bonus::StealHealth { benefiting_player } => (*benefiting_player, true),
+            _ => continue,
+        };
+        if score.player(benefiting_player).health == 0 {
+            continue;
+        }
+
+        // The point is stolen from the opponent with the most health,
+        // taking its last one ends the round like a goal would
+        if stolen {
+            let victim = score
+                .players
+                .iter()
+                .enumerate()
+                .map(|(i, p)| (PlayerId(i), p.health))
+                .filter(|(p, health)| *p != benefiting_player && rules.plays(*p) && *health > 0)
+                .max_by_key(|(_, health)| *health)
+                .map(|(p, _)| p);
+            match victim {
+                Some(victim) => score.player_mut(victim).health -= 1,
+                None => continue,
+            }
+        }
+
+        // The health never goes over the one the side starts with
+        let max_health = rules.handicap(benefiting_player).health;
+        let player = score.player_mut(benefiting_player);
+        if player.health < max_health {
+            player.health += 1;
+            for (entity, _) in lifebars_query.iter().filter(|(_, p)| **p == benefiting_player) {
+                let timer = Timer::from_seconds(LIFEBAR_GAIN_DURATION, false);
+                commands.entity(entity).insert(LifebarGain { timer });
+            }
+        }
+    }
+}
+
+/// The lifebars swell and glow green for a moment when they gain health.
+pub fn animate_lifebar_gains(
+    mut commands: Commands,
+    time: Res<Time>,
+    theme: Res<Theme>,
+    mut lifebars_query: Query<(
+        Entity,
+        &PlayerId,
+        &mut Transform,
+        &mut TextureAtlasSprite,
+        &mut LifebarGain,
+    )>,
+) {
+    for (entity, player, mut transform, mut sprite, mut gain) in lifebars_query.iter_mut() {
+        let color = theme.paddle_color(*player);
+        if gain.timer.tick(time.delta()).finished() {
+            transform.scale = Vec3::ONE;
+            sprite.color = color;
+            commands.entity(entity).remove::<LifebarGain>();
+            continue;
+        }
+
+        let glow = (gain.timer.percent() * PI).sin();
+        transform.scale = Vec3::new(1. + 0.1 * glow, 1. + 0.4 * glow, 1.);
+        sprite.color =
+            Color::rgb(color.r() * (1. - 0.6 * glow), color.g(), color.b() * (1. - 0.6 * glow));
+    }
+}
+
+pub fn reset_lifebar_gains(
+    mut commands: Commands,
+    theme: Res<Theme>,
+    mut lifebars_query: Query<
+        (Entity, &PlayerId, &mut Transform, &mut TextureAtlasSprite),
+        With<LifebarGain>,
+    >,
+) {
+    for (entity, player, mut transform, mut sprite) in lifebars_query.iter_mut() {
+        transform.scale = Vec3::ONE;
+        sprite.color = theme.paddle_color(*player);
+        commands.entity(entity).remove::<LifebarGain>();
+    }
+}
+
+/// A lifebar that just gained health.
+#[derive(Component)]
+pub struct LifebarGain {
+    timer: Timer,
+}
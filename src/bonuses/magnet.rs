@@ -0,0 +1,165 @@
+use std::f32::consts::PI;
+
+use bevy::prelude::*;
+use bevy_kira_audio::Audio;
+use heron::prelude::*;
+use rand::Rng;
+
+use crate::game_collisions::{CollisionStatus, GameCollisionEvent};
+use crate::rules::MatchRules;
+use crate::{
+    action_just_pressed, serve_velocity, AudioAssets, Ball, BallScale, Bonus, Controller, Paddle,
+    PaddleTrack, PlayerId, TakenBonusEvent, BALL_RADIUS,
+};
+
+/// The balls a magnet catches before it wears off.
+const MAGNET_CATCHES: usize = 3;
+/// The seconds a caught ball is held before it is released on its own.
+const MAGNET_MAX_HOLD: f32 = 3.;
+
+pub fn manage_magnet_bonus(
+    mut commands: Commands,
+    mut taken_bonus_reader: EventReader<TakenBonusEvent>,
+    mut paddles_query: Query<(Entity, &PlayerId, Option<&mut Magnet>), With<Paddle>>,
+) {
+    for TakenBonusEvent { bonus, .. } in taken_bonus_reader.iter() {
+        if let Bonus::Magnet { benefiting_player } = bonus {
+            for (entity, player, magnet) in paddles_query.iter_mut() {
+                if benefiting_player == player {
+                    match magnet {
+                        Some(mut magnet) => magnet.catches = MAGNET_CATCHES,
+                        None => {
+                            commands.entity(entity).insert(Magnet { catches: MAGNET_CATCHES });
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The paddles with a magnet stop the balls they touch and hold them.
+#[allow(clippy::type_complexity)]
+pub fn catch_balls_with_magnets(
+    mut commands: Commands,
+    mut collision_events: EventReader<GameCollisionEvent>,
+    mut paddles_query: Query<(&GlobalTransform, &PaddleTrack, &mut Magnet)>,
+    mut balls_query: Query<(&GlobalTransform, &mut Velocity), (With<Ball>, Without<Caught>)>,
+    audio_assets: Res<AudioAssets>,
+    audio: Res<Audio>,
+) {
+    use GameCollisionEvent::*;
+
+    for event in collision_events.iter() {
+        if let BallAndPaddle { status: CollisionStatus::Started, ball, paddle } = event {
+            if let (Ok((paddle_transform, track, mut magnet)), Ok((ball_transform, mut velocity))) =
+                (paddles_query.get_mut(*paddle), balls_query.get_mut(*ball))
+            {
+                if magnet.catches == 0 {
+                    continue;
+                }
+                magnet.catches -= 1;
+                if magnet.catches == 0 {
+                    commands.entity(*paddle).remove::<Magnet>();
+                }
+
+                // The ball stays where it touched the paddle
+                let offset = (ball_transform.translation - paddle_transform.translation)
+                    .truncate()
+                    .dot(track.axis);
+                let speed = velocity.linear.length();
+                velocity.linear = Vec3::ZERO;
+                commands.entity(*ball).insert(Caught { paddle: *paddle, offset, speed, held: 0. });
+                audio.play(audio_assets.hit_1.clone());
+            }
+        }
+    }
+}
+
+/// Moves the caught balls with their paddle and serves them back when the action key is pressed,
+/// after a moment for the computers or once they are held for too long.
+#[allow(clippy::type_complexity)]
+pub fn carry_caught_balls(
+    mut commands: Commands,
+    time: Res<Time>,
+    rules: Res<MatchRules>,
+    ball_scale: Res<BallScale>,
+    keys: Res<Input<KeyCode>>,
+    paddles_query: Query<
+        (&GlobalTransform, &Sprite, &PaddleTrack, &Velocity, &Controller, &PlayerId),
+        Without<Ball>,
+    >,
+    mut balls_query: Query<(Entity, &mut Transform, &mut Velocity, &mut Caught), With<Ball>>,
+) {
+    let mut rng = rand::thread_rng();
+
+    for (entity, mut transform, mut velocity, mut caught) in balls_query.iter_mut() {
+        let (paddle_transform, sprite, track, paddle_velocity, controller, player) =
+            match paddles_query.get(caught.paddle) {
+                Ok(paddle) => paddle,
+                // The paddle is gone, the ball is released where it stands
+                Err(_) => {
+                    commands.entity(entity).remove::<Caught>();
+                    continue;
+                }
+            };
+
+        // The front of the paddle faces the center of the field
+        let mut normal = track.axis.perp();
+        if normal.dot(track.start) > 0. {
+            normal = -normal;
+        }
+        let half_width = sprite.custom_size.map_or(0.25, |size| size.x / 2.);
+        let position = paddle_transform.translation.truncate()
+            + track.axis * caught.offset
+            + normal * (half_width + BALL_RADIUS * ball_scale.size + 0.05);
+        transform.translation = position.extend(transform.translation.z);
+        velocity.linear = Vec3::ZERO;
+
+        caught.held += time.delta_seconds();
+        let release = match controller {
+            Controller::Computer => caught.held >= 0.5 + rules.ai.reaction_time,
+            _ => action_just_pressed(&keys, &rules, *controller),
+        };
+        if !release && caught.held < MAGNET_MAX_HOLD {
+            continue;
+        }
+
+        // The ball goes the way the paddle moves, the computers aim randomly
+        let deviation = match controller {
+            Controller::Computer => rng.gen_range(-PI / 4.0..PI / 4.),
+            _ => {
+                let along = paddle_velocity.linear.truncate().dot(track.axis);
+                let max_speed = rules.paddle_speed(*player).max(f32::EPSILON);
+                let along = (along / max_speed).clamp(-1., 1.);
+                along * PI / 4. * normal.perp_dot(track.axis).signum()
+            }
+        };
+        let speed = caught.speed.max(rules.ball_speed);
+        velocity.linear = serve_velocity(normal, deviation, speed);
+        commands.entity(entity).remove::<Caught>();
+    }
+}
+
+pub fn reset_magnets(mut commands: Commands, paddles_query: Query<Entity, With<Magnet>>) {
+    for entity in paddles_query.iter() {
+        commands.entity(entity).remove::<Magnet>();
+    }
+}
+
+/// Lets a paddle catch the balls it touches.
+#[derive(Component)]
+pub struct Magnet {
+    pub catches: usize,
+}
+
+/// A ball held by a paddle with a magnet.
+#[derive(Component)]
+pub struct Caught {
+    pub paddle: Entity,
+    /// Where the ball stands along the paddle.
+    offset: f32,
+    /// The speed of the ball when it was caught, it is served back at least as fast.
+    speed: f32,
+    held: f32,
+}
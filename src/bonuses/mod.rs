@@ -0,0 +1,19 @@
+pub use self::ball_scale::*;
+pub use self::freeze::*;
+pub use self::ghost::*;
+pub use self::health::*;
+pub use self::magnet::*;
+pub use self::portal::*;
+pub use self::reverse::*;
+pub use self::shield::*;
+pub use self::slow_motion::*;
+
+mod ball_scale;
+mod freeze;
+mod ghost;
+mod health;
+mod magnet;
+mod portal;
+mod reverse;
+mod shield;
+mod slow_motion;
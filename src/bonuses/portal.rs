@@ -0,0 +1,221 @@
+use std::f32::consts::PI;
+
+use bevy::prelude::*;
+use bevy_kira_audio::Audio;
+use heron::prelude::*;
+use rand::Rng;
+
+use crate::arena::Arena;
+use crate::game_collisions::{CollisionStatus, GameCollisionEvent, GamePhysicsLayer};
+use crate::{
+    AudioAssets, Ball, BallScale, Bonus, BonusesAssets, Caught, TakenBonusEvent, BALL_RADIUS,
+};
+
+const PORTAL_DURATION: f32 = 15.;
+const PORTAL_RADIUS: f32 = 0.5;
+/// How far from each other the two portals of a pair open.
+const PORTAL_MIN_DISTANCE: f32 = 4.;
+/// The seconds a ball ignores the portals once out of one.
+const PORTAL_COOLDOWN: f32 = 0.25;
+const PORTAL_COLORS: [Color; 2] = [Color::rgb(1., 0.6, 0.15), Color::rgb(0.25, 0.65, 1.)];
+
+pub fn manage_portals_bonus(
+    mut commands: Commands,
+    arena: Res<Arena>,
+    bonuses_assets: Res<BonusesAssets>,
+    mut taken_bonus_reader: EventReader<TakenBonusEvent>,
+    portals_query: Query<Entity, With<Portal>>,
+) {
+    let mut rng = rand::thread_rng();
+
+    for TakenBonusEvent { bonus, .. } in taken_bonus_reader.iter() {
+        if let Bonus::Portals = bonus {
+            // A new pair replaces the one already open
+            for entity in portals_query.iter() {
+                commands.entity(entity).despawn_recursive();
+            }
+
+            // The portals stand far apart, across the center of the field, clear of the obstacles
+            let area = arena.bonus_area;
+            let mut random_position =
+                || Vec2::new(rng.gen_range(-area.x..area.x), rng.gen_range(-area.y..area.y));
+            let positions = (0..50)
+                .map(|_| {
+                    let first = random_position();
+                    (first, (-first + random_position() / 4.).clamp(-area, area))
+                })
+                .find(|(first, second)| {
+                    first.distance(*second) >= PORTAL_MIN_DISTANCE
+                        && arena.is_clear(*first, PORTAL_RADIUS)
+                        && arena.is_clear(*second, PORTAL_RADIUS)
+                })
+                .unwrap_or((Vec2::new(-area.x, area.y), Vec2::new(area.x, -area.y)));
+
+            let entrances = [positions.0, positions.1].map(|position| {
+                let angle = rng.gen_range(0. ..2. * PI);
+                (position, angle)
+            });
+            let entities = entrances.map(|_| commands.spawn().id());
+            for (index, (position, angle)) in entrances.into_iter().enumerate() {
+                commands
+                    .entity(entities[index])
+                    .insert_bundle(SpriteSheetBundle {
+                        texture_atlas: bonuses_assets.portal_texture_atlas.clone(),
+                        transform: Transform {
+                            translation: position.extend(0.),
+                            rotation: Quat::from_rotation_z(angle),
+                            ..Default::default()
+                        },
+                        sprite: TextureAtlasSprite {
+                            index: 1,
+                            color: PORTAL_COLORS[index],
+                            custom_size: Some(Vec2::splat(PORTAL_RADIUS * 2.)),
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    })
+                    .insert(RigidBody::Sensor)
+                    .insert(CollisionShape::Sphere { radius: PORTAL_RADIUS })
+                    .insert(
+                        CollisionLayers::none()
+                            .with_group(GamePhysicsLayer::Portal)
+                            .with_mask(GamePhysicsLayer::Ball),
+                    )
+                    .insert(Portal {
+                        linked: entities[1 - index],
+                        angle,
+                        timer: Timer::from_seconds(PORTAL_DURATION, false),
+                    });
+            }
+        }
+    }
+}
+
+/// Whether a ball went through a portal since the last frame. The sensors of the portals
+/// do not take part in the continuous collision detection, a fast ball can jump over them
+/// in a single step, so its path is checked too.
+fn crossed_portal(from: Vec2, to: Vec2, center: Vec2) -> bool {
+    let path = to - from;
+    let along = if path.length_squared() > 0. {
+        ((center - from).dot(path) / path.length_squared()).clamp(0., 1.)
+    } else {
+        0.
+    };
+    (from + path * along).distance(center) < PORTAL_RADIUS
+}
+
+/// Sends the balls entering a portal out of the other one, with the same speed
+/// and their direction rotated as much as the portals are from each other.
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn teleport_balls(
+    mut commands: Commands,
+    time: Res<Time>,
+    physics_time: Res<PhysicsTime>,
+    ball_scale: Res<BallScale>,
+    mut collision_events: EventReader<GameCollisionEvent>,
+    portals_query: Query<(Entity, &Portal, &GlobalTransform)>,
+    mut balls_query: Query<
+        (Entity, &mut Transform, &mut Velocity, Option<&mut Teleported>),
+        (With<Ball>, Without<Caught>),
+    >,
+    audio_assets: Res<AudioAssets>,
+    audio: Res<Audio>,
+) {
+    use GameCollisionEvent::*;
+
+    let mut entering: Vec<_> = collision_events
+        .iter()
+        .filter_map(|event| match event {
+            BallAndPortal { status: CollisionStatus::Started, ball, portal } => {
+                Some((*ball, *portal))
+            }
+            _ => None,
+        })
+        .collect();
+
+    let step = time.delta_seconds() * physics_time.scale();
+    for (ball, transform, velocity, _) in balls_query.iter() {
+        let to = transform.translation.truncate();
+        let from = to - velocity.linear.truncate() * step;
+        for (portal, _, portal_transform) in portals_query.iter() {
+            if crossed_portal(from, to, portal_transform.translation.truncate()) {
+                entering.push((ball, portal));
+            }
+        }
+    }
+
+    for (ball, mut transform, mut velocity, teleported) in balls_query.iter_mut() {
+        // The ball just came out of a portal and may still overlap it
+        if let Some(mut teleported) = teleported {
+            if !teleported.timer.tick(time.delta()).finished() {
+                continue;
+            }
+            commands.entity(ball).remove::<Teleported>();
+        }
+
+        let portal = match entering.iter().find(|(b, _)| *b == ball) {
+            Some((_, portal)) => *portal,
+            None => continue,
+        };
+        let entrance = match portals_query.get(portal) {
+            Ok((_, entrance, _)) => entrance,
+            Err(_) => continue,
+        };
+        let (exit, exit_transform) = match portals_query.get(entrance.linked) {
+            Ok((_, exit, exit_transform)) => (exit, exit_transform),
+            Err(_) => continue,
+        };
+
+        let rotation = Quat::from_rotation_z(exit.angle - entrance.angle);
+        let linear = rotation.mul_vec3(velocity.linear);
+        let direction = linear.truncate().normalize_or_zero();
+
+        // The ball comes out beyond the exit, its new place is set on the rigid body
+        // with no sweep from the old one, so nothing on the way is hit
+        let position = exit_transform.translation.truncate()
+            + direction * (PORTAL_RADIUS + BALL_RADIUS * ball_scale.size);
+        transform.translation = position.extend(transform.translation.z);
+        velocity.linear = linear;
+        commands
+            .entity(ball)
+            .insert(Teleported { timer: Timer::from_seconds(PORTAL_COOLDOWN, false) });
+        audio.play(audio_assets.portal.clone());
+    }
+}
+
+/// The portals close when their time is over, they fade out on their last seconds.
+pub fn close_portals(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut portals_query: Query<(Entity, &mut Portal, &mut TextureAtlasSprite)>,
+) {
+    for (entity, mut portal, mut sprite) in portals_query.iter_mut() {
+        if portal.timer.tick(time.delta()).finished() {
+            commands.entity(entity).despawn_recursive();
+        } else {
+            let left = portal.timer.duration().as_secs_f32() - portal.timer.elapsed_secs();
+            sprite.color.set_a((left / 2.).min(1.));
+        }
+    }
+}
+
+pub fn reset_portals(mut commands: Commands, portals_query: Query<Entity, With<Portal>>) {
+    for entity in portals_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// One of the two portals of a pair, the balls entering it come out of the linked one.
+#[derive(Component)]
+pub struct Portal {
+    linked: Entity,
+    /// Where the portal faces, the balls turn as much as the faces of the pair differ.
+    angle: f32,
+    timer: Timer,
+}
+
+/// A ball that just went through a portal.
+#[derive(Component)]
+pub struct Teleported {
+    timer: Timer,
+}
@@ -0,0 +1,109 @@
+use bevy::prelude::*;
+use rand::Rng;
+
+use crate::{Bonus, BonusesAssets, Paddle, PlayerId, TakenBonusEvent};
+
+/// The seconds the controls of the cursed paddles stay reversed.
+const REVERSE_DURATION: f32 = 8.;
+/// The chances that a cursed computer figures out its controls for the whole curse.
+const REVERSE_AI_ADAPT_CHANCE: f64 = 0.4;
+
+pub fn manage_reverse_controls_bonus(
+    mut commands: Commands,
+    bonuses_assets: Res<BonusesAssets>,
+    mut taken_bonus_reader: EventReader<TakenBonusEvent>,
+    mut paddles_query: Query<(Entity, &PlayerId, Option<&mut Reversed>), With<Paddle>>,
+) {
+    let mut rng = rand::thread_rng();
+
+    for TakenBonusEvent { bonus, .. } in taken_bonus_reader.iter() {
+        if let Bonus::ReverseControls { benefiting_player } = bonus {
+            // Every opponent of the benefiting player is cursed
+            for (entity, player, reversed) in paddles_query.iter_mut() {
+                if benefiting_player == player {
+                    continue;
+                }
+
+                match reversed {
+                    Some(mut reversed) => reversed.timer.reset(),
+                    None => {
+                        let timer = Timer::from_seconds(REVERSE_DURATION, false);
+                        commands
+                            .entity(entity)
+                            // A cursed computer notices that its controls are reversed, or not
+                            .insert(Reversed {
+                                timer,
+                                adapted: rng.gen_bool(REVERSE_AI_ADAPT_CHANCE),
+                            })
+                            .with_children(|parent| {
+                                parent
+                                    .spawn_bundle(SpriteSheetBundle {
+                                        texture_atlas: bonuses_assets.reverse_texture_atlas.clone(),
+                                        transform: Transform::from_xyz(0., 0., 0.2),
+                                        sprite: TextureAtlasSprite {
+                                            index: 0,
+                                            custom_size: Some(Vec2::new(0.75, 0.75)),
+                                            ..Default::default()
+                                        },
+                                        ..Default::default()
+                                    })
+                                    .insert(ReversedCue);
+                            });
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Gives the cursed paddles their controls back once the curse is over.
+pub fn expire_reversed_controls(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut paddles_query: Query<(Entity, &mut Reversed, &Children)>,
+    mut cues_query: Query<&mut TextureAtlasSprite, With<ReversedCue>>,
+) {
+    for (entity, mut reversed, children) in paddles_query.iter_mut() {
+        let finished = reversed.timer.tick(time.delta()).finished();
+        for child in children.iter() {
+            if let Ok(mut sprite) = cues_query.get_mut(*child) {
+                if finished {
+                    commands.entity(*child).despawn_recursive();
+                } else {
+                    // The cue blinks faster as the curse is about to end
+                    let rate = 4. + 12. * (1. - reversed.timer.percent_left());
+                    let blink = (reversed.timer.elapsed_secs() * rate).sin();
+                    sprite.color.set_a(0.6 + 0.4 * blink.abs());
+                }
+            }
+        }
+        if finished {
+            commands.entity(entity).remove::<Reversed>();
+        }
+    }
+}
+
+pub fn reset_reversed_controls(
+    mut commands: Commands,
+    paddles_query: Query<Entity, With<Reversed>>,
+    cues_query: Query<Entity, With<ReversedCue>>,
+) {
+    for entity in paddles_query.iter() {
+        commands.entity(entity).remove::<Reversed>();
+    }
+    for entity in cues_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// A curse reversing the up and down controls of a paddle.
+#[derive(Component)]
+pub struct Reversed {
+    timer: Timer,
+    /// Whether the computer moving the paddle compensates for the curse.
+    pub adapted: bool,
+}
+
+/// The icon shown on the cursed paddles.
+#[derive(Component)]
+pub struct ReversedCue;
@@ -0,0 +1,122 @@
+use bevy::prelude::*;
+use bevy_kira_audio::Audio;
+use heron::prelude::*;
+
+use crate::arena::Arena;
+use crate::game_collisions::{CollisionStatus, GameCollisionEvent, GamePhysicsLayer};
+use crate::theme::Theme;
+use crate::{AudioAssets, Bonus, PlayerId, TakenBonusEvent};
+
+/// The seconds a shield stands in front of a goal when no ball breaks it.
+const SHIELD_DURATION: f32 = 15.;
+const SHIELD_THICKNESS: f32 = 0.3;
+
+pub fn manage_shield_bonus(
+    mut commands: Commands,
+    theme: Res<Theme>,
+    arena: Res<Arena>,
+    mut taken_bonus_reader: EventReader<TakenBonusEvent>,
+    mut shields_query: Query<(&mut Shield, &PlayerId)>,
+) {
+    for TakenBonusEvent { bonus, .. } in taken_bonus_reader.iter() {
+        if let Bonus::Shield { benefiting_player } = bonus {
+            // A second shield only gives more time to the one already standing
+            if let Some((mut shield, _)) =
+                shields_query.iter_mut().find(|(_, p)| *p == benefiting_player)
+            {
+                shield.timer.reset();
+                continue;
+            }
+
+            // The shield stands along the goal, between it and the paddles
+            let goal = arena.player(*benefiting_player).goal;
+            let thin_x = goal.size.x < goal.size.y;
+            let axis = if thin_x { Vec3::X } else { Vec3::Y };
+            let mut outward = Quat::from_rotation_z(goal.rotation).mul_vec3(axis).truncate();
+            if outward.dot(goal.position) < 0. {
+                outward = -outward;
+            }
+            let thickness = goal.size.x.min(goal.size.y);
+            let length = goal.size.x.max(goal.size.y).min(2. * arena.paddle_range);
+            let size = if thin_x {
+                Vec2::new(SHIELD_THICKNESS, length)
+            } else {
+                Vec2::new(length, SHIELD_THICKNESS)
+            };
+            let position = goal.position - outward * (thickness + SHIELD_THICKNESS) / 2.;
+
+            let mut color = theme.paddle_color(*benefiting_player);
+            color.set_a(0.6);
+            commands
+                .spawn_bundle(SpriteBundle {
+                    sprite: Sprite { color, custom_size: Some(size), ..Default::default() },
+                    // Behind the edges that it may cross
+                    transform: Transform {
+                        translation: position.extend(-0.1),
+                        rotation: Quat::from_rotation_z(goal.rotation),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                })
+                .insert(RigidBody::Static)
+                .insert(CollisionShape::Cuboid {
+                    half_extends: (size / 2.).extend(0.),
+                    border_radius: None,
+                })
+                .insert(PhysicMaterial {
+                    restitution: PhysicMaterial::PERFECTLY_ELASTIC_RESTITUTION,
+                    ..Default::default()
+                })
+                .insert(
+                    CollisionLayers::none()
+                        .with_group(GamePhysicsLayer::Shield)
+                        .with_mask(GamePhysicsLayer::Ball),
+                )
+                .insert(Shield { timer: Timer::from_seconds(SHIELD_DURATION, false) })
+                .insert(*benefiting_player);
+        }
+    }
+}
+
+/// The shields break after bouncing a ball back, or when their time is over.
+pub fn break_shields(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut collision_events: EventReader<GameCollisionEvent>,
+    mut shields_query: Query<(Entity, &mut Shield, &mut Sprite)>,
+    audio_assets: Res<AudioAssets>,
+    audio: Res<Audio>,
+) {
+    use GameCollisionEvent::*;
+
+    for event in collision_events.iter() {
+        if let BallAndShield { status: CollisionStatus::Stopped, shield, .. } = event {
+            if shields_query.get(*shield).is_ok() {
+                commands.entity(*shield).despawn_recursive();
+                audio.play(audio_assets.shield_break.clone());
+            }
+        }
+    }
+
+    for (entity, mut shield, mut sprite) in shields_query.iter_mut() {
+        shield.timer.tick(time.delta());
+        if shield.timer.finished() {
+            commands.entity(entity).despawn_recursive();
+        } else {
+            // The shield fades as its time runs out
+            sprite.color.set_a(0.2 + 0.4 * shield.timer.percent_left());
+        }
+    }
+}
+
+pub fn reset_shields(mut commands: Commands, shields_query: Query<Entity, With<Shield>>) {
+    for entity in shields_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// A barrier in front of the goal of a player, it bounces one ball back before breaking.
+#[derive(Component)]
+pub struct Shield {
+    timer: Timer,
+}
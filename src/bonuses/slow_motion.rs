@@ -0,0 +1,95 @@
+use bevy::prelude::*;
+use heron::prelude::*;
+
+use crate::hud::HUD_Z;
+use crate::{Bonus, TakenBonusEvent};
+
+const SLOW_MOTION_DURATION: f32 = 6.;
+/// How fast the physics runs during the slow motion, relative to the real time.
+const SLOW_MOTION_SCALE: f32 = 0.4;
+
+pub fn manage_slow_motion_bonus(
+    mut commands: Commands,
+    mut taken_bonus_reader: EventReader<TakenBonusEvent>,
+    mut slow_motion: ResMut<SlowMotion>,
+    overlays_query: Query<(), With<SlowMotionOverlay>>,
+) {
+    for TakenBonusEvent { bonus, .. } in taken_bonus_reader.iter() {
+        if let Bonus::SlowMotion = bonus {
+            slow_motion.timer = Some(Timer::from_seconds(SLOW_MOTION_DURATION, false));
+
+            // A grey veil over the field washes its colors out while the time is slowed,
+            // it stays under the HUD so that the lifebars and the rounds keep their colors
+            if overlays_query.is_empty() {
+                commands
+                    .spawn_bundle(SpriteBundle {
+                        sprite: Sprite {
+                            color: Color::rgba(0.5, 0.5, 0.5, 0.),
+                            custom_size: Some(Vec2::splat(200.)),
+                            ..Default::default()
+                        },
+                        transform: Transform::from_xyz(0., 0., HUD_Z - 1.),
+                        ..Default::default()
+                    })
+                    .insert(SlowMotionOverlay);
+            }
+        }
+    }
+}
+
+/// Slows the whole physics world down and brings it back to normal once the slow motion is over.
+///
+/// The scale applies to every body: the balls are the only dynamic ones so they are the only
+/// ones to slow down, their `Velocity` keeping its unscaled value. The paddles and the moving
+/// walls keep moving at full speed by their transforms, they cover more ground in each of the
+/// slowed steps and so hit the balls harder meanwhile.
+pub fn update_slow_motion(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut slow_motion: ResMut<SlowMotion>,
+    mut physics_time: ResMut<PhysicsTime>,
+    mut overlays_query: Query<(Entity, &mut Sprite), With<SlowMotionOverlay>>,
+) {
+    let timer = match slow_motion.timer.as_mut() {
+        Some(timer) => timer,
+        None => return,
+    };
+
+    if timer.tick(time.delta()).finished() {
+        slow_motion.timer = None;
+        physics_time.set_scale(1.);
+        overlays_query.iter().for_each(|(entity, _)| commands.entity(entity).despawn_recursive());
+        return;
+    }
+
+    // The slow motion eases in and out
+    let fade = (timer.elapsed_secs().min(timer.duration().as_secs_f32() - timer.elapsed_secs())
+        / 0.5)
+        .min(1.);
+    physics_time.set_scale(1. - (1. - SLOW_MOTION_SCALE) * fade);
+    for (_, mut sprite) in overlays_query.iter_mut() {
+        sprite.color.set_a(0.45 * fade);
+    }
+}
+
+pub fn reset_slow_motion(
+    mut commands: Commands,
+    mut slow_motion: ResMut<SlowMotion>,
+    mut physics_time: ResMut<PhysicsTime>,
+    overlays_query: Query<Entity, With<SlowMotionOverlay>>,
+) {
+    slow_motion.timer = None;
+    physics_time.set_scale(1.);
+    for entity in overlays_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// The slow motion of the balls, while the timer runs.
+#[derive(Default)]
+pub struct SlowMotion {
+    timer: Option<Timer>,
+}
+
+#[derive(Component)]
+pub struct SlowMotionOverlay;
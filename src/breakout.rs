@@ -0,0 +1,339 @@
+use bevy::asset::{AssetLoader, LoadContext, LoadedAsset};
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use bevy::utils::BoxedFuture;
+use bevy_kira_audio::Audio;
+use heron::prelude::*;
+use rand::prelude::SliceRandom;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::arena::Arena;
+use crate::game_collisions::{CollisionStatus, GameCollisionEvent, GamePhysicsLayer};
+use crate::hud::update_text;
+use crate::rules::{GameMode, MatchRules};
+use crate::theme::Theme;
+use crate::{load_indexed, AudioAssets, GameScore, LevelAssets, PlayerId, SpawnBonusEvent, States};
+
+/// The layout of the bricks of a breakout level, loaded from the `.level` files of the assets.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TypeUuid)]
+#[uuid = "8b0c1f7e-3d53-4a3a-9d0c-5f2f0e6a41b7"]
+#[serde(default)]
+pub struct BreakoutLevel {
+    pub name: String,
+    /// The bricks are placed relatively to the center of the side of the computers,
+    /// the x axis goes from their goal toward the center of the field.
+    pub bricks: Vec<BrickDef>,
+    /// The chances that a broken brick drops a bonus.
+    pub drop_chance: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct BrickDef {
+    pub position: Vec2,
+    pub size: Vec2,
+    /// The number of hits the brick takes before breaking.
+    pub health: usize,
+}
+
+impl Default for BreakoutLevel {
+    fn default() -> BreakoutLevel {
+        let bricks = (-4..=4)
+            .flat_map(|y| {
+                (0..3).map(move |x| BrickDef {
+                    position: Vec2::new(-3. + x as f32 * 1.2, y as f32 * 1.2),
+                    size: Vec2::new(0.8, 1.),
+                    health: 1,
+                })
+            })
+            .collect();
+        BreakoutLevel { name: String::from("Wall"), bricks, drop_chance: 0.2 }
+    }
+}
+
+#[derive(Default)]
+pub struct LevelLoader;
+
+impl AssetLoader for LevelLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            load_context.set_default_asset(LoadedAsset::new(parse_level(bytes)?));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["level"]
+    }
+}
+
+fn parse_level(bytes: &[u8]) -> Result<BreakoutLevel, anyhow::Error> {
+    let level: BreakoutLevel = ron::de::from_bytes(bytes)?;
+    if level.bricks.is_empty() {
+        anyhow::bail!("the {} level has no bricks", level.name);
+    }
+    Ok(level)
+}
+
+/// The levels listed in a `.levels` index file, in the order they are selected.
+#[derive(Debug, TypeUuid)]
+#[uuid = "c4d2a7b1-6e3f-4a95-8b1d-0f5e9a7c3d28"]
+pub struct LevelIndex(pub Vec<Handle<BreakoutLevel>>);
+
+#[derive(Default)]
+pub struct LevelIndexLoader;
+
+impl AssetLoader for LevelIndexLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let levels = load_indexed(bytes, load_context, parse_level).await?;
+            load_context.set_default_asset(LoadedAsset::new(LevelIndex(levels)));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["levels"]
+    }
+}
+
+/// The names of the levels that can be selected for a breakout.
+pub fn level_names(assets: &LevelAssets, levels: &Assets<BreakoutLevel>) -> Vec<String> {
+    assets.all().iter().filter_map(|h| levels.get(h)).map(|l| l.name.clone()).collect()
+}
+
+#[derive(Component)]
+pub struct Brick {
+    health: usize,
+    max_health: usize,
+}
+
+/// The progress of the current breakout.
+#[derive(Default)]
+pub struct BreakoutRun {
+    pub level: String,
+    pub bricks: usize,
+    pub broken: usize,
+    pub elapsed: f32,
+    drop_chance: f32,
+}
+
+/// The outcome of the last breakout.
+#[derive(Default)]
+pub struct BreakoutResults {
+    pub last: Option<BreakoutResult>,
+}
+
+#[derive(Debug, Clone)]
+pub struct BreakoutResult {
+    pub level: String,
+    pub cleared: bool,
+    pub broken: usize,
+    pub bricks: usize,
+    pub time: f32,
+}
+
+#[derive(Component, Default)]
+pub struct BreakoutText;
+
+/// Builds the bricks of the selected level on the sides of the computers.
+pub fn spawn_bricks(
+    mut commands: Commands,
+    theme: Res<Theme>,
+    rules: Res<MatchRules>,
+    arena: Res<Arena>,
+    level_assets: Res<LevelAssets>,
+    levels: Res<Assets<BreakoutLevel>>,
+    mut run: ResMut<BreakoutRun>,
+) {
+    *run = BreakoutRun::default();
+    if rules.mode != GameMode::Breakout {
+        return;
+    }
+
+    let level = level_assets
+        .all()
+        .iter()
+        .filter_map(|h| levels.get(h))
+        .find(|l| l.name == rules.level)
+        .cloned()
+        .unwrap_or_else(|| {
+            warn!("Could not find the {} level, using the default one", rules.level);
+            BreakoutLevel::default()
+        });
+    run.level = level.name.clone();
+    run.drop_chance = level.drop_chance;
+
+    for player in arena.player_ids().filter(|p| !rules.plays(*p)) {
+        let (goal, side) = (arena.player(player).goal, arena.player(player).side);
+        let inward = (-goal.position).normalize_or_zero();
+        let along = Vec2::new(-inward.y, inward.x);
+        let rotation = Quat::from_rotation_z(inward.y.atan2(inward.x));
+
+        for brick in level.bricks.iter() {
+            let position = side.position + inward * brick.position.x + along * brick.position.y;
+            commands
+                .spawn_bundle(SpriteBundle {
+                    sprite: Sprite {
+                        color: theme.obstacles,
+                        custom_size: Some(brick.size),
+                        ..Default::default()
+                    },
+                    transform: Transform {
+                        translation: position.extend(0.),
+                        rotation,
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                })
+                .insert(RigidBody::Static)
+                .insert(CollisionShape::Cuboid {
+                    half_extends: (brick.size / 2.).extend(0.),
+                    border_radius: None,
+                })
+                .insert(PhysicMaterial {
+                    restitution: PhysicMaterial::PERFECTLY_ELASTIC_RESTITUTION,
+                    ..Default::default()
+                })
+                .insert(
+                    CollisionLayers::none()
+                        .with_group(GamePhysicsLayer::Brick)
+                        .with_mask(GamePhysicsLayer::Ball),
+                )
+                .insert(Brick { health: brick.health.max(1), max_health: brick.health.max(1) });
+
+            run.bricks += 1;
+        }
+    }
+}
+
+/// Damages the bricks touched by the balls, the broken ones may drop a bonus.
+#[allow(clippy::too_many_arguments)]
+pub fn damage_bricks(
+    mut commands: Commands,
+    rules: Res<MatchRules>,
+    mut run: ResMut<BreakoutRun>,
+    mut collision_events: EventReader<GameCollisionEvent>,
+    mut spawn_bonus_event: EventWriter<SpawnBonusEvent>,
+    mut bricks_query: Query<(&mut Brick, &Transform)>,
+    audio_assets: Res<AudioAssets>,
+    audio: Res<Audio>,
+) {
+    use GameCollisionEvent::*;
+
+    if rules.mode != GameMode::Breakout {
+        return;
+    }
+
+    let mut rng = rand::thread_rng();
+    for event in collision_events.iter() {
+        if let BallAndBrick { status: CollisionStatus::Started, brick: entity, .. } = event {
+            let (mut brick, transform) = match bricks_query.get_mut(*entity) {
+                Ok(brick) => brick,
+                Err(_) => continue,
+            };
+
+            // Several balls may touch the same brick at once
+            if brick.health == 0 {
+                continue;
+            }
+
+            brick.health -= 1;
+            if brick.health > 0 {
+                continue;
+            }
+
+            commands.entity(*entity).despawn_recursive();
+            run.broken += 1;
+            audio.play(audio_assets.goal.clone());
+
+            if rng.gen_bool(run.drop_chance.clamp(0., 1.) as f64) {
+                if let Some(bonus) = rules.bonuses.choose(&mut rng) {
+                    let position = transform.translation.truncate();
+                    spawn_bonus_event.send(SpawnBonusEvent { bonus: *bonus, position });
+                }
+            }
+        }
+    }
+}
+
+/// Fades the bricks as they lose their health.
+pub fn display_bricks(theme: Res<Theme>, mut bricks_query: Query<(&mut Sprite, &Brick)>) {
+    for (mut sprite, brick) in bricks_query.iter_mut() {
+        let alpha = 0.4 + 0.6 * brick.health as f32 / brick.max_health as f32;
+        sprite.color = *theme.obstacles.clone().set_a(alpha);
+    }
+}
+
+/// Ends the breakout when every brick is broken or the player has no health left,
+/// the next level is selected when the current one is cleared.
+#[allow(clippy::too_many_arguments)]
+pub fn end_breakout(
+    time: Res<Time>,
+    mut rules: ResMut<MatchRules>,
+    score: Res<GameScore>,
+    mut run: ResMut<BreakoutRun>,
+    mut results: ResMut<BreakoutResults>,
+    mut state: ResMut<State<States>>,
+    level_assets: Res<LevelAssets>,
+    levels: Res<Assets<BreakoutLevel>>,
+) {
+    if rules.mode != GameMode::Breakout {
+        return;
+    }
+
+    run.elapsed += time.delta_seconds();
+    let cleared = run.broken >= run.bricks;
+    if !cleared && score.player(PlayerId::HUMAN).health > 0 {
+        return;
+    }
+
+    results.last = Some(BreakoutResult {
+        level: run.level.clone(),
+        cleared,
+        broken: run.broken,
+        bricks: run.bricks,
+        time: run.elapsed,
+    });
+
+    if cleared {
+        let names = level_names(&level_assets, &levels);
+        if let Some(index) = names.iter().position(|n| *n == run.level) {
+            rules.level = names[(index + 1) % names.len()].clone();
+        }
+    }
+
+    state.set(States::MatchSetup).unwrap();
+}
+
+/// Shows the outcome of the last breakout on the custom match screen.
+pub fn display_breakout_results(
+    theme: Res<Theme>,
+    rules: Res<MatchRules>,
+    results: Res<BreakoutResults>,
+    mut text_query: Query<&mut Text, With<BreakoutText>>,
+) {
+    for mut text in text_query.iter_mut() {
+        let mut value = String::new();
+        if rules.mode == GameMode::Breakout {
+            value.push_str(&format!("Breakout, next level {}\n\n", rules.level));
+            if let Some(last) = &results.last {
+                let outcome = if last.cleared { "cleared" } else { "lost" };
+                value.push_str(&format!(
+                    "{} {}\nBricks {}/{}\nTime   {:.1}s\n",
+                    last.level, outcome, last.broken, last.bricks, last.time
+                ));
+            }
+        }
+
+        update_text(&mut text, value, &theme);
+    }
+}
@@ -0,0 +1,292 @@
+use std::fs;
+use std::path::Path;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::rules::{AiPersonality, Handicap, MatchRules, RonFileError, RulesPreset};
+use crate::theme::{Theme, ThemePreset};
+use crate::{BonusType, FontAssets, GameScore, PlayerId, States};
+
+/// The file in which the progress of the campaign is kept.
+pub const CAMPAIGN_FILE: &str = "campaign.ron";
+
+/// Something the player gets by beating an opponent of the campaign.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Unlock {
+    Theme(ThemePreset),
+    /// The name of an arena.
+    Arena(String),
+}
+
+impl Unlock {
+    pub fn name(&self) -> String {
+        match self {
+            Unlock::Theme(preset) => format!("the {} theme", preset.name()),
+            Unlock::Arena(name) => format!("the {} arena", name),
+        }
+    }
+}
+
+/// An opponent of the campaign ladder.
+pub struct Opponent {
+    pub name: &'static str,
+    pub rules: MatchRules,
+    pub unlocks: Vec<Unlock>,
+}
+
+/// The opponents of the campaign, from the easiest to the hardest.
+pub fn ladder() -> Vec<Opponent> {
+    use BonusType::*;
+
+    let opponent = |name: &'static str, arena: &str, ai, bonuses: &[BonusType], unlocks| {
+        let rules = MatchRules {
+            name: String::from(name),
+            arena: String::from(arena),
+            ai,
+            bonuses: bonuses.to_vec(),
+            ..MatchRules::from(RulesPreset::Classic)
+        };
+        Opponent { name, rules, unlocks }
+    };
+
+    vec![
+        opponent(
+            "Rookie",
+            "Classic",
+            AiPersonality { dead_zone: 1.5, reaction_time: 0.3, aim_offset: 0. },
+            &[SplitBall, IncreasePaddleSize],
+            vec![Unlock::Theme(ThemePreset::Sunset)],
+        ),
+        opponent(
+            "Pinball",
+            "Bumpers",
+            AiPersonality { dead_zone: 1.2, reaction_time: 0.2, aim_offset: 0.5 },
+            &[SplitBall, BallSpeedInArea, IncreasePaddleSize],
+            vec![Unlock::Arena(String::from("Funnel"))],
+        ),
+        opponent(
+            "Mason",
+            "Moving walls",
+            AiPersonality { dead_zone: 1., reaction_time: 0.15, aim_offset: 0.8 },
+            &[SplitBall, BallSpeedInArea, ShrinkPaddleSize, IncreasePaddleSize],
+            vec![Unlock::Theme(ThemePreset::Neon)],
+        ),
+        opponent(
+            "Newton",
+            "Funnel",
+            AiPersonality { dead_zone: 0.8, reaction_time: 0.1, aim_offset: 1. },
+            &BonusType::ALL,
+            vec![Unlock::Arena(String::from("Four players"))],
+        ),
+        Opponent {
+            name: "Champion",
+            rules: MatchRules {
+                name: String::from("Champion"),
+                rounds_to_win: 3,
+                ai: AiPersonality { dead_zone: 0.5, reaction_time: 0.05, aim_offset: 1.5 },
+                computer: Handicap::new(6., 10),
+                ..MatchRules::from(RulesPreset::Chaos)
+            },
+            unlocks: vec![Unlock::Theme(ThemePreset::Phosphor)],
+        },
+    ]
+}
+
+/// The progress of the player through the campaign, saved between games.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CampaignProgress {
+    /// The number of opponents beaten, the next one is the only one unlocked.
+    pub beaten: usize,
+    pub unlocked: Vec<Unlock>,
+}
+
+impl CampaignProgress {
+    pub fn load(path: impl AsRef<Path>) -> Result<CampaignProgress, RonFileError> {
+        let content = fs::read_to_string(path)?;
+        Ok(ron::from_str(&content)?)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), RonFileError> {
+        let content = ron::ser::to_string_pretty(self, Default::default())?;
+        Ok(fs::write(path, content)?)
+    }
+
+    /// Only the themes and arenas rewarded by the campaign are locked.
+    pub fn is_unlocked(&self, unlock: &Unlock) -> bool {
+        self.unlocked.contains(unlock)
+            || !ladder().iter().any(|opponent| opponent.unlocks.contains(unlock))
+    }
+}
+
+/// The state of the campaign screen and the opponent being played.
+#[derive(Default)]
+pub struct Campaign {
+    selected: usize,
+    playing: Option<usize>,
+    message: String,
+}
+
+#[derive(Component)]
+pub struct CampaignScreen;
+
+#[derive(Component)]
+pub struct CampaignText;
+
+pub fn spawn_campaign_screen(
+    mut commands: Commands,
+    assets: Res<FontAssets>,
+    theme: Res<Theme>,
+    progress: Res<CampaignProgress>,
+    mut campaign: ResMut<Campaign>,
+) {
+    campaign.selected = progress.beaten.min(ladder().len() - 1);
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            color: Color::NONE.into(),
+            ..Default::default()
+        })
+        .insert(CampaignScreen)
+        .with_children(|parent| {
+            let style = TextStyle {
+                font: assets.monogram.clone(),
+                font_size: 24.,
+                color: theme.field_lines,
+            };
+
+            parent
+                .spawn_bundle(TextBundle {
+                    text: Text {
+                        sections: vec![TextSection { value: String::new(), style }],
+                        alignment: Default::default(),
+                    },
+                    ..Default::default()
+                })
+                .insert(CampaignText);
+        });
+}
+
+pub fn despawn_campaign_screen(
+    mut commands: Commands,
+    screen_query: Query<Entity, With<CampaignScreen>>,
+) {
+    for entity in screen_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+pub fn edit_campaign(
+    mut keys: ResMut<Input<KeyCode>>,
+    mut state: ResMut<State<States>>,
+    mut rules: ResMut<MatchRules>,
+    mut campaign: ResMut<Campaign>,
+    progress: Res<CampaignProgress>,
+) {
+    let ladder = ladder();
+    // The beaten opponents can be played again
+    let unlocked = (progress.beaten + 1).min(ladder.len());
+
+    if keys.clear_just_pressed(KeyCode::Up) {
+        campaign.selected = campaign.selected.saturating_sub(1);
+    } else if keys.clear_just_pressed(KeyCode::Down) {
+        campaign.selected = (campaign.selected + 1).min(unlocked - 1);
+    } else if keys.clear_just_pressed(KeyCode::Return) {
+        *rules = ladder[campaign.selected].rules.clone();
+        campaign.playing = Some(campaign.selected);
+        campaign.message.clear();
+        state.set(States::InitGame).unwrap();
+    } else if keys.clear_just_pressed(KeyCode::Escape) {
+        state.set(States::MatchSetup).unwrap();
+    }
+}
+
+/// Records the outcome of a campaign match and goes back to the campaign screen.
+pub fn finish_campaign_match(
+    rules: Res<MatchRules>,
+    score: Res<GameScore>,
+    mut campaign: ResMut<Campaign>,
+    mut progress: ResMut<CampaignProgress>,
+    mut state: ResMut<State<States>>,
+) {
+    let index = match campaign.playing {
+        Some(index) => index,
+        None => return,
+    };
+    campaign.playing = None;
+
+    let ladder = ladder();
+    let opponent = &ladder[index];
+    campaign.message = if score.winner(&rules) != Some(PlayerId::HUMAN) {
+        format!("{} won, try again!", opponent.name)
+    } else if index == progress.beaten {
+        progress.beaten += 1;
+        progress.unlocked.extend(opponent.unlocks.iter().cloned());
+        let unlocks: Vec<_> = opponent.unlocks.iter().map(Unlock::name).collect();
+        if let Err(e) = progress.save(CAMPAIGN_FILE) {
+            warn!("Could not save the campaign: {}", e);
+        }
+        format!("You beat {} and unlocked {}!", opponent.name, unlocks.join(" and "))
+    } else {
+        format!("You beat {} again!", opponent.name)
+    };
+
+    state.set(States::Campaign).unwrap();
+}
+
+pub fn display_campaign(
+    theme: Res<Theme>,
+    campaign: Res<Campaign>,
+    progress: Res<CampaignProgress>,
+    mut text_query: Query<&mut Text, With<CampaignText>>,
+    added_query: Query<(), Added<CampaignText>>,
+) {
+    let changed = campaign.is_changed() || progress.is_changed() || theme.is_changed();
+    if !changed && added_query.is_empty() {
+        return;
+    }
+
+    for mut text in text_query.iter_mut() {
+        let style = text.sections[0].style.clone();
+        let highlight = TextStyle { color: theme.paddle_color(PlayerId::HUMAN), ..style.clone() };
+
+        let mut sections =
+            vec![TextSection { value: String::from("Campaign\n\n"), style: style.clone() }];
+
+        for (i, opponent) in ladder().iter().enumerate() {
+            let (cursor, style) = if i == campaign.selected {
+                ("> ", highlight.clone())
+            } else {
+                ("  ", style.clone())
+            };
+            let status = if i < progress.beaten {
+                "beaten"
+            } else if i == progress.beaten {
+                "next"
+            } else {
+                "locked"
+            };
+            sections.push(TextSection {
+                value: format!(
+                    "{}{:<10}{:<14}{:<8}\n",
+                    cursor, opponent.name, opponent.rules.arena, status
+                ),
+                style,
+            });
+        }
+
+        sections.push(TextSection {
+            value: format!("\nUp/Down select  Enter play  Escape back\n{}", campaign.message),
+            style,
+        });
+
+        text.sections = sections;
+    }
+}
@@ -0,0 +1,408 @@
+use bevy::prelude::*;
+
+use crate::arena::{arena_names, find_arena, Arena};
+use crate::breakout::{level_names, BreakoutLevel};
+use crate::campaign::{CampaignProgress, Unlock};
+use crate::editor::ArenaEditor;
+use crate::rules::{Drill, GameMode, MatchRules, RulesPreset, TeamLayout, Training, RULES_FILE};
+use crate::theme::Theme;
+use crate::{ArenaAssets, FontAssets, LevelAssets, PlayerId, States};
+
+/// The screen where the rules of the next match can be tweaked.
+#[derive(Component)]
+pub struct CustomMatchScreen;
+
+#[derive(Component)]
+pub struct CustomMatchText;
+
+/// The state of the custom match screen: the selected field and
+/// the preset that will be applied when cycling through them.
+#[derive(Default)]
+pub struct CustomMatchSelection {
+    field: usize,
+    preset: Option<RulesPreset>,
+    message: String,
+}
+
+#[derive(Clone, Copy)]
+enum RulesField {
+    Mode,
+    RoundsToWin,
+    BallSpeed,
+    BallMaxSpeed,
+    PaddleSpeedUp,
+    EdgeSpeedUp,
+    PaddleSpeed,
+    BonusMinDelay,
+    BonusMaxDelay,
+    BonusSlots,
+    MaxBonuses,
+    Arena,
+    Level,
+    TeamLayout,
+    Teammate,
+    Opponent,
+    Drill,
+    LauncherSpeed,
+    LauncherSpread,
+    LauncherInterval,
+    Handicap(PlayerId, HandicapField),
+}
+
+#[derive(Clone, Copy)]
+enum HandicapField {
+    PaddleHeight,
+    PaddleSpeed,
+    Health,
+    BonusBias,
+}
+
+impl RulesField {
+    const ALL: [RulesField; 28] = [
+        RulesField::Mode,
+        RulesField::RoundsToWin,
+        RulesField::BallSpeed,
+        RulesField::BallMaxSpeed,
+        RulesField::PaddleSpeedUp,
+        RulesField::EdgeSpeedUp,
+        RulesField::PaddleSpeed,
+        RulesField::BonusMinDelay,
+        RulesField::BonusMaxDelay,
+        RulesField::BonusSlots,
+        RulesField::MaxBonuses,
+        RulesField::Arena,
+        RulesField::Level,
+        RulesField::TeamLayout,
+        RulesField::Teammate,
+        RulesField::Opponent,
+        RulesField::Drill,
+        RulesField::LauncherSpeed,
+        RulesField::LauncherSpread,
+        RulesField::LauncherInterval,
+        RulesField::Handicap(PlayerId::HUMAN, HandicapField::PaddleHeight),
+        RulesField::Handicap(PlayerId::HUMAN, HandicapField::PaddleSpeed),
+        RulesField::Handicap(PlayerId::HUMAN, HandicapField::Health),
+        RulesField::Handicap(PlayerId::HUMAN, HandicapField::BonusBias),
+        RulesField::Handicap(PlayerId(1), HandicapField::PaddleHeight),
+        RulesField::Handicap(PlayerId(1), HandicapField::PaddleSpeed),
+        RulesField::Handicap(PlayerId(1), HandicapField::Health),
+        RulesField::Handicap(PlayerId(1), HandicapField::BonusBias),
+    ];
+
+    fn label(&self) -> String {
+        let label = match self {
+            RulesField::Mode => "Mode",
+            RulesField::RoundsToWin => "Rounds to win",
+            RulesField::BallSpeed => "Ball speed",
+            RulesField::BallMaxSpeed => "Ball max speed",
+            RulesField::PaddleSpeedUp => "Paddle speed-up",
+            RulesField::EdgeSpeedUp => "Edge speed-up",
+            RulesField::PaddleSpeed => "Paddle speed",
+            RulesField::BonusMinDelay => "Bonus min delay",
+            RulesField::BonusMaxDelay => "Bonus max delay",
+            RulesField::BonusSlots => "Bonus slots",
+            RulesField::MaxBonuses => "Max bonuses",
+            RulesField::Arena => "Arena",
+            RulesField::Level => "Breakout level",
+            RulesField::TeamLayout => "Teams",
+            RulesField::Teammate => "Teammate",
+            RulesField::Opponent => "Opponent",
+            RulesField::Drill => "Training drill",
+            RulesField::LauncherSpeed => "Launcher speed",
+            RulesField::LauncherSpread => "Launcher angle",
+            RulesField::LauncherInterval => "Launcher delay",
+            RulesField::Handicap(player, field) => {
+                let side = if player.is_human() { "Player" } else { "Computer" };
+                let field = match field {
+                    HandicapField::PaddleHeight => "paddle",
+                    HandicapField::PaddleSpeed => "speed",
+                    HandicapField::Health => "health",
+                    HandicapField::BonusBias => "bonus bias",
+                };
+                return format!("{} {}", side, field);
+            }
+        };
+        label.to_string()
+    }
+
+    fn value(&self, rules: &MatchRules) -> String {
+        match self {
+            RulesField::Mode => rules.mode.name().to_string(),
+            RulesField::RoundsToWin => rules.rounds_to_win.to_string(),
+            RulesField::BallSpeed => format!("{:.1}", rules.ball_speed),
+            RulesField::BallMaxSpeed => format!("{:.0}", rules.ball_max_speed),
+            RulesField::PaddleSpeedUp => format!("{:.1}%", rules.ball_touch_paddle_speed_up * 100.),
+            RulesField::EdgeSpeedUp => format!("{:.2}%", rules.ball_touch_edge_speed_up * 100.),
+            RulesField::PaddleSpeed => format!("{:.1}", rules.paddle_speed),
+            RulesField::BonusMinDelay => format!("{:.0}s", rules.bonus_min_delay),
+            RulesField::BonusMaxDelay => format!("{:.0}s", rules.bonus_max_delay),
+            RulesField::BonusSlots if rules.bonus_slots == 0 => String::from("None (instant)"),
+            RulesField::BonusSlots => rules.bonus_slots.to_string(),
+            RulesField::MaxBonuses => rules.max_bonuses.to_string(),
+            RulesField::Arena => rules.arena.clone(),
+            RulesField::Level => rules.level.clone(),
+            RulesField::TeamLayout => rules.team_layout.name().to_string(),
+            RulesField::Teammate if rules.human_teammate => String::from("Human (arrows)"),
+            RulesField::Teammate => String::from("Computer"),
+            RulesField::Opponent if rules.human_opponent => String::from("Human (arrows)"),
+            RulesField::Opponent => String::from("Computer"),
+            RulesField::Drill => rules.training.drill.name().to_string(),
+            RulesField::LauncherSpeed => format!("{:.1}", rules.training.speed),
+            RulesField::LauncherSpread => format!("{:.0}°", rules.training.spread),
+            RulesField::LauncherInterval => format!("{:.1}s", rules.training.interval),
+            RulesField::Handicap(player, field) => {
+                let handicap = rules.handicap(*player);
+                match field {
+                    HandicapField::PaddleHeight => format!("{:.1}", handicap.paddle_height),
+                    HandicapField::PaddleSpeed => format!("x{:.1}", handicap.paddle_speed),
+                    HandicapField::Health => handicap.health.to_string(),
+                    HandicapField::BonusBias => format!("x{:.1}", handicap.bonus_bias),
+                }
+            }
+        }
+    }
+
+    fn adjust(&self, rules: &mut MatchRules, step: f32, arenas: &[String], levels: &[String]) {
+        fn add(value: usize, step: f32, min: usize, max: usize) -> usize {
+            ((value as f32 + step) as usize).clamp(min, max)
+        }
+
+        match self {
+            RulesField::Mode => {
+                let modes = GameMode::ALL;
+                let index = modes.iter().position(|m| *m == rules.mode).unwrap_or(0);
+                let offset = if step < 0. { modes.len() - 1 } else { 1 };
+                rules.mode = modes[(index + offset) % modes.len()];
+            }
+            RulesField::RoundsToWin => rules.rounds_to_win = add(rules.rounds_to_win, step, 1, 5),
+            RulesField::BallSpeed => {
+                rules.ball_speed = (rules.ball_speed + step).clamp(2., rules.ball_max_speed)
+            }
+            RulesField::BallMaxSpeed => {
+                rules.ball_max_speed =
+                    (rules.ball_max_speed + step * 10.).clamp(rules.ball_speed, 300.)
+            }
+            RulesField::PaddleSpeedUp => {
+                rules.ball_touch_paddle_speed_up =
+                    (rules.ball_touch_paddle_speed_up + step * 0.005).clamp(0., 0.2)
+            }
+            RulesField::EdgeSpeedUp => {
+                rules.ball_touch_edge_speed_up =
+                    (rules.ball_touch_edge_speed_up + step * 0.0025).clamp(0., 0.1)
+            }
+            RulesField::PaddleSpeed => {
+                rules.paddle_speed = (rules.paddle_speed + step).clamp(2., 40.)
+            }
+            RulesField::BonusMinDelay => {
+                rules.bonus_min_delay =
+                    (rules.bonus_min_delay + step).clamp(1., rules.bonus_max_delay)
+            }
+            RulesField::BonusMaxDelay => {
+                rules.bonus_max_delay =
+                    (rules.bonus_max_delay + step).clamp(rules.bonus_min_delay, 120.)
+            }
+            RulesField::BonusSlots => rules.bonus_slots = add(rules.bonus_slots, step, 0, 5),
+            RulesField::MaxBonuses => rules.max_bonuses = add(rules.max_bonuses, step, 1, 12),
+            RulesField::Arena if !arenas.is_empty() => {
+                let index = arenas.iter().position(|a| *a == rules.arena).unwrap_or(0);
+                let offset = if step < 0. { arenas.len() - 1 } else { 1 };
+                rules.arena = arenas[(index + offset) % arenas.len()].clone();
+            }
+            RulesField::Arena => (),
+            RulesField::Level if !levels.is_empty() => {
+                let index = levels.iter().position(|l| *l == rules.level).unwrap_or(0);
+                let offset = if step < 0. { levels.len() - 1 } else { 1 };
+                rules.level = levels[(index + offset) % levels.len()].clone();
+            }
+            RulesField::Level => (),
+            RulesField::TeamLayout => {
+                let layouts = TeamLayout::ALL;
+                let index = layouts.iter().position(|l| *l == rules.team_layout).unwrap_or(0);
+                let offset = if step < 0. { layouts.len() - 1 } else { 1 };
+                rules.team_layout = layouts[(index + offset) % layouts.len()];
+            }
+            RulesField::Teammate => rules.human_teammate = !rules.human_teammate,
+            RulesField::Opponent => rules.human_opponent = !rules.human_opponent,
+            RulesField::Drill => {
+                let drills = Drill::ALL;
+                let index = drills.iter().position(|d| *d == rules.training.drill).unwrap_or(0);
+                let offset = if step < 0. { drills.len() - 1 } else { 1 };
+                rules.training = Training::from(drills[(index + offset) % drills.len()]);
+            }
+            RulesField::LauncherSpeed => {
+                rules.training.speed = (rules.training.speed + step).clamp(2., rules.ball_max_speed)
+            }
+            RulesField::LauncherSpread => {
+                rules.training.spread = (rules.training.spread + step * 5.).clamp(0., 120.)
+            }
+            RulesField::LauncherInterval => {
+                rules.training.interval = (rules.training.interval + step * 0.5).clamp(0.5, 10.)
+            }
+            RulesField::Handicap(player, field) => {
+                let handicap = rules.handicap_mut(*player);
+                match field {
+                    HandicapField::PaddleHeight => {
+                        handicap.paddle_height =
+                            (handicap.paddle_height + step * 0.5).clamp(1., 10.)
+                    }
+                    HandicapField::PaddleSpeed => {
+                        handicap.paddle_speed = (handicap.paddle_speed + step * 0.1).clamp(0.2, 3.)
+                    }
+                    HandicapField::Health => handicap.health = add(handicap.health, step, 1, 99),
+                    HandicapField::BonusBias => {
+                        handicap.bonus_bias = (handicap.bonus_bias + step * 0.5).clamp(0., 5.)
+                    }
+                }
+            }
+        }
+    }
+}
+
+pub fn spawn_custom_match_screen(
+    mut commands: Commands,
+    assets: Res<FontAssets>,
+    theme: Res<Theme>,
+) {
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            color: Color::NONE.into(),
+            ..Default::default()
+        })
+        .insert(CustomMatchScreen)
+        .with_children(|parent| {
+            let style = TextStyle {
+                font: assets.monogram.clone(),
+                font_size: 24.,
+                color: theme.field_lines,
+            };
+
+            parent
+                .spawn_bundle(TextBundle {
+                    text: Text {
+                        sections: vec![TextSection { value: String::new(), style }],
+                        alignment: Default::default(),
+                    },
+                    ..Default::default()
+                })
+                .insert(CustomMatchText);
+        });
+}
+
+pub fn despawn_custom_match_screen(
+    mut commands: Commands,
+    screen_query: Query<Entity, With<CustomMatchScreen>>,
+) {
+    for entity in screen_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn edit_custom_match(
+    mut keys: ResMut<Input<KeyCode>>,
+    mut state: ResMut<State<States>>,
+    mut rules: ResMut<MatchRules>,
+    mut selection: ResMut<CustomMatchSelection>,
+    mut editor: ResMut<ArenaEditor>,
+    arena_assets: Res<ArenaAssets>,
+    arenas: Res<Assets<Arena>>,
+    level_assets: Res<LevelAssets>,
+    levels: Res<Assets<BreakoutLevel>>,
+    progress: Res<CampaignProgress>,
+) {
+    let fields_count = RulesField::ALL.len();
+    let mut arena_names = arena_names(&arena_assets, &arenas);
+    arena_names.retain(|name| progress.is_unlocked(&Unlock::Arena(name.clone())));
+    let level_names = level_names(&level_assets, &levels);
+
+    if keys.clear_just_pressed(KeyCode::Up) {
+        selection.field = (selection.field + fields_count - 1) % fields_count;
+    } else if keys.clear_just_pressed(KeyCode::Down) {
+        selection.field = (selection.field + 1) % fields_count;
+    } else if keys.clear_just_pressed(KeyCode::Left) {
+        RulesField::ALL[selection.field].adjust(&mut rules, -1., &arena_names, &level_names);
+        rules.name = String::from("Custom");
+    } else if keys.clear_just_pressed(KeyCode::Right) {
+        RulesField::ALL[selection.field].adjust(&mut rules, 1., &arena_names, &level_names);
+        rules.name = String::from("Custom");
+    } else if keys.clear_just_pressed(KeyCode::P) {
+        let preset = selection.preset.map_or(RulesPreset::Chaos, |p| p.next());
+        *rules = MatchRules::from(preset);
+        selection.preset = Some(preset);
+    } else if keys.clear_just_pressed(KeyCode::S) {
+        selection.message = match rules.save(RULES_FILE) {
+            Ok(()) => format!("Saved to {}", RULES_FILE),
+            Err(e) => format!("Could not save: {}", e),
+        };
+    } else if keys.clear_just_pressed(KeyCode::L) {
+        selection.message = match MatchRules::load(RULES_FILE) {
+            Ok(loaded) => {
+                *rules = loaded;
+                format!("Loaded {}", RULES_FILE)
+            }
+            Err(e) => format!("Could not load: {}", e),
+        };
+    } else if keys.clear_just_pressed(KeyCode::E) {
+        // Edits a copy of the selected arena
+        editor.arena =
+            find_arena(&arena_assets, &arenas, &rules.arena).cloned().unwrap_or_default();
+        state.set(States::ArenaEditor).unwrap();
+    } else if keys.clear_just_pressed(KeyCode::C) {
+        state.set(States::Campaign).unwrap();
+    } else if keys.clear_just_pressed(KeyCode::B) {
+        state.set(States::Tournament).unwrap();
+    } else if keys.clear_just_pressed(KeyCode::Return) {
+        state.set(States::InitGame).unwrap();
+    }
+}
+
+pub fn display_custom_match(
+    theme: Res<Theme>,
+    rules: Res<MatchRules>,
+    selection: Res<CustomMatchSelection>,
+    mut text_query: Query<&mut Text, With<CustomMatchText>>,
+    added_query: Query<(), Added<CustomMatchText>>,
+) {
+    let changed = rules.is_changed() || selection.is_changed() || theme.is_changed();
+    if !changed && added_query.is_empty() {
+        return;
+    }
+
+    for mut text in text_query.iter_mut() {
+        let style = text.sections[0].style.clone();
+        let highlight = TextStyle { color: theme.paddle_color(PlayerId::HUMAN), ..style.clone() };
+
+        let mut sections = vec![TextSection {
+            value: format!("Custom match - {}\n\n", rules.name),
+            style: style.clone(),
+        }];
+
+        for (i, field) in RulesField::ALL.iter().enumerate() {
+            let (cursor, style) = if i == selection.field {
+                ("> ", highlight.clone())
+            } else {
+                ("  ", style.clone())
+            };
+            sections.push(TextSection {
+                value: format!("{}{:<18}{}\n", cursor, field.label(), field.value(&rules)),
+                style,
+            });
+        }
+
+        sections.push(TextSection {
+            value: format!(
+                "\nUp/Down select  Left/Right change  P preset\nS save  L load  E edit arena  C campaign  B tournament  Enter play\n{}",
+                selection.message
+            ),
+            style,
+        });
+
+        text.sections = sections;
+    }
+}
@@ -0,0 +1,202 @@
+use bevy::prelude::*;
+use ordered_float::OrderedFloat;
+use rand::prelude::SliceRandom;
+use rand::Rng;
+
+use crate::arena::Arena;
+use crate::rules::{GameMode, MatchRules};
+use crate::survival::SurvivalRun;
+use crate::{Ball, BonusType, BonusesAssets, GameScore, Paddle, PlayerId, SpawnBonusEvent};
+
+/// How much more the bonuses favor a side that lost all the health the leader still has.
+const CATCH_UP: f32 = 2.;
+/// The bonuses helping a side that is behind to come back.
+const COMEBACK_BONUSES: [BonusType; 3] =
+    [BonusType::ExtraLife, BonusType::StealHealth, BonusType::Shield];
+/// How far from the balls, the paddles and the other bonuses a bonus tries to spawn.
+const BONUS_SPACING: f32 = 2.;
+/// The seconds a warning shows where a bonus is about to spawn.
+const TELEGRAPH_DURATION: f32 = 1.;
+
+/// Decides which bonus spawns, when and where, one bonus at a time.
+#[derive(Default)]
+pub struct BonusDirector {
+    timer: Timer,
+}
+
+/// The warning shown where a bonus is about to spawn.
+#[derive(Component)]
+pub struct BonusTelegraph {
+    bonus: BonusType,
+    timer: Timer,
+}
+
+/// How far behind the side with the most health a side is, from 0 to 1.
+fn health_deficit(score: &GameScore, rules: &MatchRules, player: PlayerId) -> f32 {
+    let ratio = |i: usize| {
+        let max_health = rules.handicap(PlayerId(i)).health.max(1);
+        score.players[i].health as f32 / max_health as f32
+    };
+    let leader = (0..score.players.len()).map(ratio).fold(0., f32::max);
+    (leader - ratio(player.0)).max(0.)
+}
+
+/// The weight of a bonus, the comeback ones come more often when a side is far behind.
+fn bonus_weight(bonus: BonusType, score: &GameScore, rules: &MatchRules) -> f32 {
+    if !COMEBACK_BONUSES.contains(&bonus) || !rules.mode.has_opponents() {
+        return rules.bonus_weight(bonus);
+    }
+
+    let deficit = (0..score.players.len())
+        .map(|i| health_deficit(score, rules, PlayerId(i)))
+        .fold(0., f32::max);
+    rules.bonus_weight(bonus) * (1. + CATCH_UP * deficit)
+}
+
+/// The seconds until the next bonus: every bonus spawns once per delay of the rules
+/// on average, as often as its weight says.
+fn next_delay(rules: &MatchRules, survival: &SurvivalRun) -> f32 {
+    let weights: f32 = rules.bonuses.iter().map(|bonus| rules.bonus_weight(*bonus)).sum();
+    if weights <= 0. {
+        return rules.bonus_max_delay;
+    }
+
+    // The bonuses come faster as the survival waves go by
+    let factor = survival.speed_multiplier();
+    let delay = rand::thread_rng().gen_range(rules.bonus_min_delay..=rules.bonus_max_delay);
+    delay / factor / weights
+}
+
+pub fn reset_bonus_director(
+    mut director: ResMut<BonusDirector>,
+    rules: Res<MatchRules>,
+    survival: Res<SurvivalRun>,
+) {
+    director.timer = Timer::from_seconds(next_delay(&rules, &survival), false);
+}
+
+/// Picks the next bonus and its place, then shows a warning there until it spawns.
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn direct_bonus_spawns(
+    mut commands: Commands,
+    time: Res<Time>,
+    rules: Res<MatchRules>,
+    survival: Res<SurvivalRun>,
+    arena: Res<Arena>,
+    score: Res<GameScore>,
+    assets: Res<BonusesAssets>,
+    mut director: ResMut<BonusDirector>,
+    bonuses_query: Query<&GlobalTransform, With<BonusType>>,
+    telegraphs_query: Query<&GlobalTransform, With<BonusTelegraph>>,
+    bodies_query: Query<&GlobalTransform, Or<(With<Ball>, With<Paddle>)>>,
+) {
+    // The bonuses of the breakout are dropped by the bricks
+    if rules.mode == GameMode::Breakout {
+        return;
+    }
+
+    if !director.timer.tick(time.delta()).finished() {
+        return;
+    }
+    director.timer = Timer::from_seconds(next_delay(&rules, &survival), false);
+
+    // The field holds a limited number of bonuses, the next one waits for some to be taken
+    let on_field = bonuses_query.iter().count() + telegraphs_query.iter().count();
+    if on_field >= rules.max_bonuses {
+        return;
+    }
+
+    let mut rng = rand::thread_rng();
+    let bonus = match rules.bonuses.choose_weighted(&mut rng, |b| bonus_weight(*b, &score, &rules))
+    {
+        Ok(bonus) => *bonus,
+        Err(_) => return,
+    };
+
+    // The side is chosen first, the ones that are behind more often
+    let players: Vec<_> = arena.player_ids().collect();
+    let side = players
+        .choose_weighted(&mut rng, |p| {
+            let catch_up = if rules.mode.has_opponents() {
+                1. + CATCH_UP * health_deficit(&score, &rules, *p)
+            } else {
+                1.
+            };
+            rules.handicap(*p).bonus_bias * catch_up
+        })
+        .map(|p| arena.player(*p).side)
+        .ok();
+
+    // Then the place of the bonus area inside it that is the farthest from everything else,
+    // the first one far enough will do
+    let area = arena.bonus_area;
+    let mut random_position =
+        || Vec2::new(rng.gen_range(-area.x..area.x), rng.gen_range(-area.y..area.y));
+    let others: Vec<_> = bonuses_query
+        .iter()
+        .chain(telegraphs_query.iter())
+        .chain(bodies_query.iter())
+        .map(|transform| transform.translation.truncate())
+        .collect();
+    let spacing = |position: Vec2| {
+        others.iter().map(|other| other.distance(position)).fold(f32::INFINITY, f32::min)
+    };
+    let candidates: Vec<_> = (0..20)
+        .map(|_| random_position())
+        .filter(|p| side.is_none_or(|side| side.contains(*p)))
+        .collect();
+    let position = candidates
+        .iter()
+        .find(|p| spacing(**p) >= BONUS_SPACING)
+        .or_else(|| candidates.iter().max_by_key(|p| OrderedFloat(spacing(**p))))
+        .copied()
+        .unwrap_or_else(random_position);
+
+    commands
+        .spawn_bundle(SpriteSheetBundle {
+            texture_atlas: assets.warning_texture_atlas.clone(),
+            transform: Transform::from_translation(position.extend(0.)),
+            sprite: TextureAtlasSprite {
+                index: 0,
+                custom_size: Some(Vec2::new(0.75, 0.75)),
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .insert(BonusTelegraph { bonus, timer: Timer::from_seconds(TELEGRAPH_DURATION, false) });
+}
+
+/// The warnings blink faster and faster, then leave their place to the bonus.
+pub fn hatch_bonus_telegraphs(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut spawn_bonus_event: EventWriter<SpawnBonusEvent>,
+    mut telegraphs_query: Query<(
+        Entity,
+        &GlobalTransform,
+        &mut TextureAtlasSprite,
+        &mut BonusTelegraph,
+    )>,
+) {
+    for (entity, transform, mut sprite, mut telegraph) in telegraphs_query.iter_mut() {
+        if telegraph.timer.tick(time.delta()).finished() {
+            let position = transform.translation.truncate();
+            spawn_bonus_event.send(SpawnBonusEvent { bonus: telegraph.bonus, position });
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+
+        let elapsed = telegraph.timer.elapsed_secs();
+        let blink = (elapsed * (6. + 18. * telegraph.timer.percent())).sin();
+        sprite.color.set_a(0.55 + 0.45 * blink);
+    }
+}
+
+pub fn reset_bonus_telegraphs(
+    mut commands: Commands,
+    telegraphs_query: Query<Entity, With<BonusTelegraph>>,
+) {
+    for entity in telegraphs_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
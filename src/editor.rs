@@ -0,0 +1,621 @@
+use std::f32::consts::{FRAC_PI_2, PI};
+
+use bevy::prelude::*;
+
+use crate::arena::{Arena, ArenaRect};
+use crate::init::VIEW_HALF_HEIGHT;
+use crate::obstacles::ObstacleDef;
+use crate::rules::MatchRules;
+use crate::theme::Theme;
+use crate::{ArenaAssets, FontAssets, HudAssets, PlayerId, States};
+
+/// The file the editor saves its arena to, it is loaded as the custom arena.
+pub const EDITOR_ARENA_FILE: &str = "assets/arenas/custom.arena";
+
+/// The smallest width, height or radius an edited shape can have.
+const MIN_SHAPE_SIZE: f32 = 0.2;
+const OUTLINE_WIDTH: f32 = 0.05;
+/// The width of the paddle tracks and the size of the points of the moving walls, to pick them.
+const HANDLE_SIZE: f32 = 0.3;
+
+// The colors of heron's `debug-2d` feature, to preview the collision shapes the same way
+const STATIC_SHAPE_COLOR: Color = Color::rgba(0.64, 0., 0.16, 0.4);
+const SENSOR_SHAPE_COLOR: Color = Color::rgba(0., 0.63, 0., 0.4);
+const KINEMATIC_SHAPE_COLOR: Color = Color::rgba(0.21, 0.07, 0.7, 0.4);
+
+/// The arena being edited, it only replaces the `Arena` of the matches when playtested.
+#[derive(Default)]
+pub struct ArenaEditor {
+    pub arena: Arena,
+    pub playtesting: bool,
+    /// The arena of the rules before the playtest, given back once it ends.
+    previous_arena: Option<String>,
+    tool: EditorTool,
+    selected: Option<ArenaItem>,
+    drag: Option<Drag>,
+    message: String,
+}
+
+/// What a click on an empty place of the arena adds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum EditorTool {
+    #[default]
+    Wall,
+    Block,
+    Bumper,
+    MovingWall,
+}
+
+impl EditorTool {
+    fn next(&self) -> EditorTool {
+        match self {
+            EditorTool::Wall => EditorTool::Block,
+            EditorTool::Block => EditorTool::Bumper,
+            EditorTool::Bumper => EditorTool::MovingWall,
+            EditorTool::MovingWall => EditorTool::Wall,
+        }
+    }
+}
+
+/// An editable part of the arena.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ArenaItem {
+    Edge(usize),
+    Goal(PlayerId),
+    /// The track of the paddles of a player: moving it moves their start,
+    /// resizing it changes the range of every paddle.
+    Track(PlayerId),
+    Obstacle(usize),
+    /// A point of the path of a moving wall, by obstacle and point index.
+    PathPoint(usize, usize),
+}
+
+#[derive(Clone, Copy)]
+enum Drag {
+    /// Moves the item, keeping the offset between its center and the cursor.
+    Move(Vec2),
+    Resize,
+}
+
+enum ItemShape {
+    Rect(ArenaRect),
+    Circle(Vec2, f32),
+}
+
+impl ItemShape {
+    fn center(&self) -> Vec2 {
+        match self {
+            ItemShape::Rect(rect) => rect.position,
+            ItemShape::Circle(position, _) => *position,
+        }
+    }
+
+    fn contains(&self, point: Vec2) -> bool {
+        match self {
+            ItemShape::Rect(rect) => rect.contains(point),
+            ItemShape::Circle(position, radius) => point.distance(*position) <= *radius,
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct EditorEntity;
+
+#[derive(Component)]
+pub struct EditorShape;
+
+#[derive(Component)]
+pub struct EditorText;
+
+impl ArenaEditor {
+    fn shape(&self, item: ArenaItem) -> Option<ItemShape> {
+        let arena = &self.arena;
+        match item {
+            ArenaItem::Edge(index) => arena.edges.get(index).map(|rect| ItemShape::Rect(*rect)),
+            ArenaItem::Goal(player) => {
+                arena.players.get(player.0).map(|player| ItemShape::Rect(player.goal))
+            }
+            ArenaItem::Track(player) => arena.players.get(player.0).map(|p| {
+                let (start, length) = (p.paddle_start, 2. * arena.paddle_range);
+                let rect = ArenaRect::new(start.x, start.y, HANDLE_SIZE, length);
+                ItemShape::Rect(ArenaRect { rotation: p.paddle_rotation, ..rect })
+            }),
+            ArenaItem::PathPoint(index, point) => match arena.obstacles.get(index) {
+                Some(ObstacleDef::MovingWall { path, .. }) => path.get(point).map(|point| {
+                    ItemShape::Rect(ArenaRect::new(point.x, point.y, HANDLE_SIZE, HANDLE_SIZE))
+                }),
+                _ => None,
+            },
+            ArenaItem::Obstacle(index) => {
+                arena.obstacles.get(index).map(|obstacle| match obstacle {
+                    ObstacleDef::Block { position, size } => {
+                        ItemShape::Rect(ArenaRect::new(position.x, position.y, size.x, size.y))
+                    }
+                    ObstacleDef::Bumper { position, radius, .. } => {
+                        ItemShape::Circle(*position, *radius)
+                    }
+                    ObstacleDef::MovingWall { path, size, .. } => {
+                        let start = path.first().copied().unwrap_or_default();
+                        ItemShape::Rect(ArenaRect::new(start.x, start.y, size.x, size.y))
+                    }
+                })
+            }
+        }
+    }
+
+    /// Every item, the ones drawn on top first.
+    fn items(&self) -> Vec<ArenaItem> {
+        let points = self.arena.obstacles.iter().enumerate().rev().flat_map(|(index, obstacle)| {
+            let points = match obstacle {
+                ObstacleDef::MovingWall { path, .. } => path.len(),
+                _ => 0,
+            };
+            (0..points).rev().map(move |point| ArenaItem::PathPoint(index, point))
+        });
+        let obstacles = (0..self.arena.obstacles.len()).rev().map(ArenaItem::Obstacle);
+        let tracks = self.arena.player_ids().map(ArenaItem::Track);
+        let edges = (0..self.arena.edges.len()).rev().map(ArenaItem::Edge);
+        let goals = self.arena.player_ids().map(ArenaItem::Goal);
+        points.chain(obstacles).chain(tracks).chain(edges).chain(goals).collect()
+    }
+
+    fn item_at(&self, point: Vec2) -> Option<ArenaItem> {
+        self.items().into_iter().find(|item| self.shape(*item).is_some_and(|s| s.contains(point)))
+    }
+
+    fn rect_mut(&mut self, item: ArenaItem) -> Option<&mut ArenaRect> {
+        match item {
+            ArenaItem::Edge(index) => self.arena.edges.get_mut(index),
+            ArenaItem::Goal(player) => self.arena.players.get_mut(player.0).map(|p| &mut p.goal),
+            _ => None,
+        }
+    }
+
+    fn add_item(&mut self, tool: EditorTool, position: Vec2) -> ArenaItem {
+        match tool {
+            EditorTool::Wall => {
+                self.arena.edges.push(ArenaRect::new(position.x, position.y, 4., 0.5));
+                ArenaItem::Edge(self.arena.edges.len() - 1)
+            }
+            EditorTool::Block => {
+                let size = Vec2::new(1., 1.);
+                self.arena.obstacles.push(ObstacleDef::Block { position, size });
+                ArenaItem::Obstacle(self.arena.obstacles.len() - 1)
+            }
+            EditorTool::Bumper => {
+                let bumper = ObstacleDef::Bumper { position, radius: 0.6, speed_up: 0.1 };
+                self.arena.obstacles.push(bumper);
+                ArenaItem::Obstacle(self.arena.obstacles.len() - 1)
+            }
+            EditorTool::MovingWall => {
+                let path = vec![position, position + Vec2::new(0., 4.)];
+                let wall = ObstacleDef::MovingWall { path, size: Vec2::new(0.4, 2.), speed: 3. };
+                self.arena.obstacles.push(wall);
+                ArenaItem::Obstacle(self.arena.obstacles.len() - 1)
+            }
+        }
+    }
+
+    /// Adds a point to the path of the selected moving wall, after the selected point
+    /// or at its end.
+    fn add_path_point(&mut self, item: ArenaItem) -> Option<ArenaItem> {
+        let (index, after) = match item {
+            ArenaItem::Obstacle(index) => (index, None),
+            ArenaItem::PathPoint(index, point) => (index, Some(point)),
+            _ => return None,
+        };
+        match &mut self.arena.obstacles[index] {
+            ObstacleDef::MovingWall { path, .. } => {
+                let after = after.unwrap_or(path.len() - 1);
+                path.insert(after + 1, path[after] + Vec2::new(2., 0.));
+                Some(ArenaItem::PathPoint(index, after + 1))
+            }
+            _ => None,
+        }
+    }
+
+    /// Turns the track of a player a quarter turn, the paddles move the same way both ways.
+    fn rotate_track(&mut self, item: ArenaItem) -> bool {
+        match item {
+            ArenaItem::Track(player) => {
+                let rotation = &mut self.arena.players[player.0].paddle_rotation;
+                *rotation = (*rotation + FRAC_PI_2) % PI;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn move_item(&mut self, item: ArenaItem, center: Vec2) {
+        if let Some(rect) = self.rect_mut(item) {
+            rect.position = center;
+        } else if let ArenaItem::Track(player) = item {
+            self.arena.players[player.0].paddle_start = center;
+        } else if let ArenaItem::PathPoint(index, point) = item {
+            if let ObstacleDef::MovingWall { path, .. } = &mut self.arena.obstacles[index] {
+                path[point] = center;
+            }
+        } else if let ArenaItem::Obstacle(index) = item {
+            match &mut self.arena.obstacles[index] {
+                ObstacleDef::Block { position, .. } | ObstacleDef::Bumper { position, .. } => {
+                    *position = center
+                }
+                // The whole path follows its start point
+                ObstacleDef::MovingWall { path, .. } => {
+                    let offset = center - path.first().copied().unwrap_or_default();
+                    path.iter_mut().for_each(|point| *point += offset);
+                }
+            }
+        }
+    }
+
+    /// Resizes the item so that its border goes through the cursor.
+    fn resize_item(&mut self, item: ArenaItem, cursor: Vec2) {
+        let shape = match self.shape(item) {
+            Some(shape) => shape,
+            None => return,
+        };
+
+        let new_size = match &shape {
+            ItemShape::Rect(rect) => {
+                let local = rect.local_position(cursor);
+                (local.abs() * 2.).max(Vec2::splat(MIN_SHAPE_SIZE))
+            }
+            ItemShape::Circle(position, _) => {
+                Vec2::splat(cursor.distance(*position).max(MIN_SHAPE_SIZE))
+            }
+        };
+
+        if let Some(rect) = self.rect_mut(item) {
+            rect.size = new_size;
+        } else if let ArenaItem::Track(_) = item {
+            self.arena.paddle_range = new_size.y / 2.;
+        } else if let ArenaItem::Obstacle(index) = item {
+            match &mut self.arena.obstacles[index] {
+                ObstacleDef::Block { size, .. } | ObstacleDef::MovingWall { size, .. } => {
+                    *size = new_size
+                }
+                ObstacleDef::Bumper { radius, .. } => *radius = new_size.x,
+            }
+        }
+    }
+
+    fn delete_item(&mut self, item: ArenaItem) -> bool {
+        match item {
+            ArenaItem::Edge(index) => {
+                self.arena.edges.remove(index);
+            }
+            ArenaItem::Obstacle(index) => {
+                self.arena.obstacles.remove(index);
+            }
+            // A moving wall goes between two points at least
+            ArenaItem::PathPoint(index, point) => match &mut self.arena.obstacles[index] {
+                ObstacleDef::MovingWall { path, .. } if path.len() > 2 => {
+                    path.remove(point);
+                }
+                _ => return false,
+            },
+            // Each side needs its goal and its paddles
+            ArenaItem::Goal(_) | ArenaItem::Track(_) => return false,
+        }
+        true
+    }
+}
+
+/// Converts the position of the cursor in the window to a position in the arena.
+fn cursor_position(windows: &Windows) -> Option<Vec2> {
+    let window = windows.get_primary()?;
+    let cursor = window.cursor_position()?;
+    let pixels_per_unit = window.height() / (2. * VIEW_HALF_HEIGHT);
+    Some((cursor - Vec2::new(window.width(), window.height()) / 2.) / pixels_per_unit)
+}
+
+pub fn end_playtest(mut editor: ResMut<ArenaEditor>, mut rules: ResMut<MatchRules>) {
+    if editor.playtesting {
+        editor.playtesting = false;
+        if let Some(previous) = editor.previous_arena.take() {
+            rules.arena = previous;
+        }
+    }
+}
+
+/// Goes back to the editor when playtesting its arena.
+pub fn stop_playtest(
+    mut keys: ResMut<Input<KeyCode>>,
+    mut state: ResMut<State<States>>,
+    editor: Res<ArenaEditor>,
+) {
+    if editor.playtesting && keys.clear_just_pressed(KeyCode::Escape) {
+        state.set(States::ArenaEditor).unwrap();
+    }
+}
+
+pub fn spawn_editor_text(mut commands: Commands, assets: Res<FontAssets>, theme: Res<Theme>) {
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect { left: Val::Px(10.), top: Val::Px(10.), ..Default::default() },
+                ..Default::default()
+            },
+            text: Text::with_section(
+                "",
+                TextStyle {
+                    font: assets.monogram.clone(),
+                    font_size: 20.,
+                    color: theme.field_lines,
+                },
+                Default::default(),
+            ),
+            ..Default::default()
+        })
+        .insert(EditorText)
+        .insert(EditorEntity);
+}
+
+pub fn despawn_editor(mut commands: Commands, entities_query: Query<Entity, With<EditorEntity>>) {
+    for entity in entities_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+pub fn edit_arena_with_mouse(
+    windows: Res<Windows>,
+    buttons: Res<Input<MouseButton>>,
+    mut last_cursor: Local<Vec2>,
+    mut editor: ResMut<ArenaEditor>,
+) {
+    let cursor = match cursor_position(&windows) {
+        Some(cursor) => cursor,
+        None => return,
+    };
+
+    let (left, right) = (MouseButton::Left, MouseButton::Right);
+    if buttons.just_pressed(left) || buttons.just_pressed(right) {
+        let item = match editor.item_at(cursor) {
+            Some(item) => item,
+            None if buttons.just_pressed(left) => {
+                let tool = editor.tool;
+                editor.add_item(tool, cursor)
+            }
+            None => {
+                editor.selected = None;
+                return;
+            }
+        };
+
+        let center = editor.shape(item).map_or(cursor, |shape| shape.center());
+        editor.selected = Some(item);
+        editor.drag = Some(if buttons.just_pressed(left) {
+            Drag::Move(center - cursor)
+        } else {
+            Drag::Resize
+        });
+    } else if buttons.just_released(left) || buttons.just_released(right) {
+        if editor.drag.is_some() {
+            editor.drag = None;
+        }
+    } else if let (Some(item), Some(drag)) = (editor.selected, editor.drag) {
+        if cursor != *last_cursor {
+            match drag {
+                Drag::Move(offset) => editor.move_item(item, cursor + offset),
+                Drag::Resize => editor.resize_item(item, cursor),
+            }
+        }
+    }
+
+    *last_cursor = cursor;
+}
+
+pub fn edit_arena_with_keys(
+    mut keys: ResMut<Input<KeyCode>>,
+    mut state: ResMut<State<States>>,
+    mut editor: ResMut<ArenaEditor>,
+    mut arena: ResMut<Arena>,
+    mut rules: ResMut<MatchRules>,
+    arena_assets: Res<ArenaAssets>,
+    mut arenas: ResMut<Assets<Arena>>,
+) {
+    if keys.clear_just_pressed(KeyCode::Tab) {
+        editor.tool = editor.tool.next();
+    } else if keys.clear_just_pressed(KeyCode::Delete) || keys.clear_just_pressed(KeyCode::Back) {
+        if let Some(item) = editor.selected {
+            if editor.delete_item(item) {
+                editor.selected = None;
+                editor.drag = None;
+            } else {
+                editor.message = match item {
+                    ArenaItem::PathPoint(..) => String::from("A moving wall needs two points"),
+                    _ => String::from("The goals and the tracks can only be moved or resized"),
+                };
+            }
+        }
+    } else if keys.clear_just_pressed(KeyCode::A) {
+        if let Some(item) = editor.selected {
+            match editor.add_path_point(item) {
+                Some(point) => editor.selected = Some(point),
+                None => editor.message = String::from("Only the moving walls have points"),
+            }
+        }
+    } else if keys.clear_just_pressed(KeyCode::R) {
+        if let Some(item) = editor.selected {
+            if !editor.rotate_track(item) {
+                editor.message = String::from("Only the paddle tracks can be turned");
+            }
+        }
+    } else if keys.clear_just_pressed(KeyCode::S) {
+        editor.arena.name = String::from("Custom");
+        editor.message = match editor.arena.save(EDITOR_ARENA_FILE) {
+            Ok(()) => {
+                // Makes the saved arena selectable without reloading the assets
+                if let Some(custom) = arenas.get_mut(&arena_assets.custom) {
+                    *custom = editor.arena.clone();
+                }
+                format!("Saved to {}", EDITOR_ARENA_FILE)
+            }
+            Err(e) => format!("Could not save: {}", e),
+        };
+    } else if keys.clear_just_pressed(KeyCode::P) {
+        *arena = editor.arena.clone();
+        let previous = std::mem::replace(&mut rules.arena, editor.arena.name.clone());
+        editor.previous_arena = Some(previous);
+        editor.playtesting = true;
+        editor.message.clear();
+        state.set(States::InitGame).unwrap();
+    } else if keys.clear_just_pressed(KeyCode::Escape) {
+        state.set(States::MatchSetup).unwrap();
+    }
+}
+
+fn spawn_rect(commands: &mut Commands, rect: ArenaRect, color: Color, z: f32) -> Entity {
+    let mut transform = rect.transform();
+    transform.translation.z = z;
+    commands
+        .spawn_bundle(SpriteBundle {
+            sprite: Sprite { color, custom_size: Some(rect.size), ..Default::default() },
+            transform,
+            ..Default::default()
+        })
+        .insert(EditorShape)
+        .insert(EditorEntity)
+        .id()
+}
+
+/// Draws the collision shapes of the edited arena, the selected item is outlined.
+pub fn display_editor_shapes(
+    mut commands: Commands,
+    editor: Res<ArenaEditor>,
+    theme: Res<Theme>,
+    rules: Res<MatchRules>,
+    hud_assets: Res<HudAssets>,
+    shapes_query: Query<Entity, With<EditorShape>>,
+    added_query: Query<(), Added<EditorText>>,
+) {
+    if !editor.is_changed() && !theme.is_changed() && added_query.is_empty() {
+        return;
+    }
+
+    for entity in shapes_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    let arena = &editor.arena;
+
+    for line in arena.field_lines.iter() {
+        spawn_rect(&mut commands, *line, theme.field_lines, 0.);
+    }
+
+    for player in arena.player_ids() {
+        let start = arena.paddle_start(player);
+        let height = rules.handicap(player).paddle_height;
+        let rotation = arena.player(player).paddle_rotation;
+        spawn_rect(
+            &mut commands,
+            ArenaRect { rotation, ..ArenaRect::new(start.x, start.y, 0.3, height) },
+            theme.paddle_color(player),
+            0.,
+        );
+    }
+
+    for item in editor.items().into_iter().rev() {
+        let outline_color = if editor.selected == Some(item) {
+            theme.paddle_color(PlayerId::HUMAN)
+        } else {
+            theme.field_lines
+        };
+        let shape_color = match item {
+            ArenaItem::Goal(_) => SENSOR_SHAPE_COLOR,
+            ArenaItem::Obstacle(index) => match arena.obstacles[index] {
+                ObstacleDef::MovingWall { .. } => KINEMATIC_SHAPE_COLOR,
+                _ => STATIC_SHAPE_COLOR,
+            },
+            // The paddles are kinematic bodies, the points are not shapes of their own
+            ArenaItem::Track(_) => KINEMATIC_SHAPE_COLOR,
+            ArenaItem::PathPoint(..) => outline_color,
+            ArenaItem::Edge(_) => STATIC_SHAPE_COLOR,
+        };
+
+        match editor.shape(item) {
+            Some(ItemShape::Rect(rect)) => {
+                // The points of the moving walls stand above the walls
+                let z = if let ArenaItem::PathPoint(..) = item { 1.5 } else { 1. };
+                let entity = spawn_rect(&mut commands, rect, shape_color, z);
+                commands.entity(entity).with_children(|parent| {
+                    let half_size = rect.size / 2.;
+                    let outlines = [
+                        (Vec2::new(0., half_size.y), Vec2::new(rect.size.x, OUTLINE_WIDTH)),
+                        (Vec2::new(0., -half_size.y), Vec2::new(rect.size.x, OUTLINE_WIDTH)),
+                        (Vec2::new(half_size.x, 0.), Vec2::new(OUTLINE_WIDTH, rect.size.y)),
+                        (Vec2::new(-half_size.x, 0.), Vec2::new(OUTLINE_WIDTH, rect.size.y)),
+                    ];
+                    for (position, size) in outlines {
+                        parent.spawn_bundle(SpriteBundle {
+                            sprite: Sprite {
+                                color: outline_color,
+                                custom_size: Some(size),
+                                ..Default::default()
+                            },
+                            transform: Transform::from_translation(position.extend(0.1)),
+                            ..Default::default()
+                        });
+                    }
+                });
+            }
+            Some(ItemShape::Circle(position, radius)) => {
+                commands
+                    .spawn_bundle(SpriteSheetBundle {
+                        texture_atlas: hud_assets.texture_atlas.clone(),
+                        sprite: TextureAtlasSprite {
+                            index: 2,
+                            color: outline_color,
+                            custom_size: Some(Vec2::splat(radius * 2.)),
+                            ..Default::default()
+                        },
+                        transform: Transform::from_translation(position.extend(1.)),
+                        ..Default::default()
+                    })
+                    .insert(EditorShape)
+                    .insert(EditorEntity);
+            }
+            None => (),
+        }
+    }
+}
+
+pub fn display_editor_text(
+    editor: Res<ArenaEditor>,
+    theme: Res<Theme>,
+    mut text_query: Query<&mut Text, With<EditorText>>,
+    added_query: Query<(), Added<EditorText>>,
+) {
+    if !editor.is_changed() && !theme.is_changed() && added_query.is_empty() {
+        return;
+    }
+
+    let selected = match editor.selected {
+        Some(ArenaItem::Edge(index)) => format!("wall {}", index + 1),
+        Some(ArenaItem::Goal(player)) => format!("goal of player {}", player.0 + 1),
+        Some(ArenaItem::Track(player)) => format!("track of player {}", player.0 + 1),
+        Some(ArenaItem::PathPoint(index, point)) => {
+            format!("point {} of moving wall {}", point + 1, index + 1)
+        }
+        Some(ArenaItem::Obstacle(index)) => match editor.arena.obstacles[index] {
+            ObstacleDef::Block { .. } => format!("block {}", index + 1),
+            ObstacleDef::Bumper { .. } => format!("bumper {}", index + 1),
+            ObstacleDef::MovingWall { .. } => format!("moving wall {}", index + 1),
+        },
+        None => String::from("none"),
+    };
+
+    for mut text in text_query.iter_mut() {
+        let section = &mut text.sections[0];
+        section.style.color = theme.field_lines;
+        section.value = format!(
+            "Arena editor - {}\nTool: {:?}  Selected: {}\n\n\
+             Left click place/move  Right click resize  Delete remove  Tab tool\n\
+             A add a point to a moving wall  R turn a paddle track\n\
+             P playtest (Escape to come back)  S save  Escape quit\n{}",
+            editor.arena.name, editor.tool, selected, editor.message
+        );
+    }
+}
@@ -1,13 +1,75 @@
 use bevy::prelude::*;
 use heron::prelude::*;
 
+#[allow(clippy::enum_variant_names)]
 #[derive(Debug, Copy, Clone)]
 pub enum GameCollisionEvent {
-    BallAndPaddle { status: CollisionStatus, ball: Entity, paddle: Entity },
-    BallAndGoal { status: CollisionStatus, ball: Entity, goal: Entity },
-    BallAndEdge { status: CollisionStatus, ball: Entity, edge: Entity },
-    BallAndBonus { status: CollisionStatus, ball: Entity, bonus: Entity },
-    BallAndSide { status: CollisionStatus, ball: Entity, side: Entity },
+    BallAndPaddle {
+        status: CollisionStatus,
+        ball: Entity,
+        paddle: Entity,
+    },
+    BallAndGoal {
+        status: CollisionStatus,
+        ball: Entity,
+        goal: Entity,
+    },
+    #[allow(dead_code)]
+    BallAndEdge {
+        status: CollisionStatus,
+        ball: Entity,
+        edge: Entity,
+    },
+    BallAndBonus {
+        status: CollisionStatus,
+        ball: Entity,
+        bonus: Entity,
+    },
+    BallAndSide {
+        status: CollisionStatus,
+        ball: Entity,
+        side: Entity,
+    },
+    #[allow(dead_code)]
+    BallAndObstacle {
+        status: CollisionStatus,
+        ball: Entity,
+        obstacle: Entity,
+    },
+    BallAndBumper {
+        status: CollisionStatus,
+        ball: Entity,
+        bumper: Entity,
+    },
+    #[allow(dead_code)]
+    BallAndMovingWall {
+        status: CollisionStatus,
+        ball: Entity,
+        wall: Entity,
+    },
+    #[allow(dead_code)]
+    BallAndTarget {
+        status: CollisionStatus,
+        ball: Entity,
+        target: Entity,
+    },
+    #[allow(dead_code)]
+    BallAndBrick {
+        status: CollisionStatus,
+        ball: Entity,
+        brick: Entity,
+    },
+    #[allow(dead_code)]
+    BallAndShield {
+        status: CollisionStatus,
+        ball: Entity,
+        shield: Entity,
+    },
+    BallAndPortal {
+        status: CollisionStatus,
+        ball: Entity,
+        portal: Entity,
+    },
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -24,6 +86,13 @@ pub enum GamePhysicsLayer {
     Edge,
     Side,
     Bonus,
+    Obstacle,
+    Bumper,
+    MovingWall,
+    Target,
+    Brick,
+    Shield,
+    Portal,
 }
 
 pub fn produce_game_collision_events(
@@ -63,6 +132,41 @@ pub fn produce_game_collision_events(
             out_events.send(BallAndSide { status, ball: entity_2, side: entity_1 });
         } else if is_side_layer(layers_2) && is_ball_layer(layers_1) {
             out_events.send(BallAndSide { status, ball: entity_1, side: entity_2 });
+        // ball and obstacle collide
+        } else if is_obstacle_layer(layers_1) && is_ball_layer(layers_2) {
+            out_events.send(BallAndObstacle { status, ball: entity_2, obstacle: entity_1 });
+        } else if is_obstacle_layer(layers_2) && is_ball_layer(layers_1) {
+            out_events.send(BallAndObstacle { status, ball: entity_1, obstacle: entity_2 });
+        // ball and bumper collide
+        } else if is_bumper_layer(layers_1) && is_ball_layer(layers_2) {
+            out_events.send(BallAndBumper { status, ball: entity_2, bumper: entity_1 });
+        } else if is_bumper_layer(layers_2) && is_ball_layer(layers_1) {
+            out_events.send(BallAndBumper { status, ball: entity_1, bumper: entity_2 });
+        // ball and moving wall collide
+        } else if is_moving_wall_layer(layers_1) && is_ball_layer(layers_2) {
+            out_events.send(BallAndMovingWall { status, ball: entity_2, wall: entity_1 });
+        } else if is_moving_wall_layer(layers_2) && is_ball_layer(layers_1) {
+            out_events.send(BallAndMovingWall { status, ball: entity_1, wall: entity_2 });
+        // ball and target collide
+        } else if is_target_layer(layers_1) && is_ball_layer(layers_2) {
+            out_events.send(BallAndTarget { status, ball: entity_2, target: entity_1 });
+        } else if is_target_layer(layers_2) && is_ball_layer(layers_1) {
+            out_events.send(BallAndTarget { status, ball: entity_1, target: entity_2 });
+        // ball and brick collide
+        } else if is_brick_layer(layers_1) && is_ball_layer(layers_2) {
+            out_events.send(BallAndBrick { status, ball: entity_2, brick: entity_1 });
+        } else if is_brick_layer(layers_2) && is_ball_layer(layers_1) {
+            out_events.send(BallAndBrick { status, ball: entity_1, brick: entity_2 });
+        // ball and shield collide
+        } else if is_shield_layer(layers_1) && is_ball_layer(layers_2) {
+            out_events.send(BallAndShield { status, ball: entity_2, shield: entity_1 });
+        } else if is_shield_layer(layers_2) && is_ball_layer(layers_1) {
+            out_events.send(BallAndShield { status, ball: entity_1, shield: entity_2 });
+        // ball and portal collide
+        } else if is_portal_layer(layers_1) && is_ball_layer(layers_2) {
+            out_events.send(BallAndPortal { status, ball: entity_2, portal: entity_1 });
+        } else if is_portal_layer(layers_2) && is_ball_layer(layers_1) {
+            out_events.send(BallAndPortal { status, ball: entity_1, portal: entity_2 });
         }
     }
 }
@@ -90,3 +194,31 @@ fn is_side_layer(layers: CollisionLayers) -> bool {
 fn is_bonus_layer(layers: CollisionLayers) -> bool {
     layers.contains_group(GamePhysicsLayer::Bonus)
 }
+
+fn is_obstacle_layer(layers: CollisionLayers) -> bool {
+    layers.contains_group(GamePhysicsLayer::Obstacle)
+}
+
+fn is_bumper_layer(layers: CollisionLayers) -> bool {
+    layers.contains_group(GamePhysicsLayer::Bumper)
+}
+
+fn is_moving_wall_layer(layers: CollisionLayers) -> bool {
+    layers.contains_group(GamePhysicsLayer::MovingWall)
+}
+
+fn is_target_layer(layers: CollisionLayers) -> bool {
+    layers.contains_group(GamePhysicsLayer::Target)
+}
+
+fn is_brick_layer(layers: CollisionLayers) -> bool {
+    layers.contains_group(GamePhysicsLayer::Brick)
+}
+
+fn is_shield_layer(layers: CollisionLayers) -> bool {
+    layers.contains_group(GamePhysicsLayer::Shield)
+}
+
+fn is_portal_layer(layers: CollisionLayers) -> bool {
+    layers.contains_group(GamePhysicsLayer::Portal)
+}
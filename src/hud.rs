@@ -0,0 +1,229 @@
+use bevy::prelude::*;
+use bevy::window::WindowResized;
+use heron::prelude::*;
+
+use crate::arena::Arena;
+use crate::breakout::BreakoutRun;
+use crate::init::VIEW_HALF_HEIGHT;
+use crate::inventory::BonusSlots;
+use crate::rules::{GameMode, MatchRules};
+use crate::survival::SurvivalRun;
+use crate::targets::TargetPractice;
+use crate::theme::Theme;
+use crate::training::TrainingStats;
+use crate::{Ball, FontAssets, GameScore, Magnet, PlayerId, Shield};
+
+/// The depth of the sprites of the HUD, above the field and what covers it.
+pub const HUD_Z: f32 = 2.;
+
+/// The height of the HUD texts, in world units.
+const HUD_FONT_HEIGHT: f32 = 0.6;
+
+/// A text of the HUD, anchored to a position of the arena (in world units)
+/// so that it follows the arena when the window is resized.
+#[derive(Component)]
+pub struct HudText {
+    kind: HudTextKind,
+    anchor: Vec2,
+}
+
+#[derive(Clone, Copy)]
+enum HudTextKind {
+    Health(PlayerId),
+    Round,
+    Rally,
+    BallSpeed,
+}
+
+pub fn spawn_hud_texts(
+    mut commands: Commands,
+    assets: Res<FontAssets>,
+    theme: Res<Theme>,
+    rules: Res<MatchRules>,
+    arena: Res<Arena>,
+) {
+    let mut texts = vec![
+        (HudTextKind::Round, Vec2::new(-11.25, -6.15)),
+        (HudTextKind::Rally, Vec2::new(-1.5, -6.15)),
+        (HudTextKind::BallSpeed, Vec2::new(7.5, -6.15)),
+    ];
+
+    // The health is written at the outer end of the lifebars
+    for player in arena.player_ids().filter(|p| rules.plays(*p)) {
+        let position = arena.player(player).hud_position;
+        let offset = if position.x > 0. { Vec2::new(3.2, 0.275) } else { Vec2::new(-4.9, 0.275) };
+        texts.push((HudTextKind::Health(player), position + offset));
+    }
+
+    for (kind, anchor) in texts {
+        commands
+            .spawn_bundle(TextBundle {
+                style: Style { position_type: PositionType::Absolute, ..Default::default() },
+                text: Text::with_section(
+                    "",
+                    TextStyle {
+                        font: assets.monogram.clone(),
+                        font_size: 20.,
+                        color: theme.field_lines,
+                    },
+                    Default::default(),
+                ),
+                ..Default::default()
+            })
+            .insert(HudText { kind, anchor });
+    }
+}
+
+/// Places the HUD texts at their arena anchors and scales them with the window.
+pub fn layout_hud_texts(
+    windows: Res<Windows>,
+    mut resize_events: EventReader<WindowResized>,
+    mut texts_query: Query<(&mut Style, &mut Text, &HudText)>,
+    added_query: Query<(), Added<HudText>>,
+) {
+    let resized = resize_events.iter().count() > 0;
+    if !resized && added_query.is_empty() {
+        return;
+    }
+
+    let window = match windows.get_primary() {
+        Some(window) => window,
+        None => return,
+    };
+
+    let (width, height) = (window.width(), window.height());
+    let pixels_per_unit = height / (2. * VIEW_HALF_HEIGHT);
+
+    for (mut style, mut text, hud_text) in texts_query.iter_mut() {
+        style.position = Rect {
+            left: Val::Px(width / 2. + hud_text.anchor.x * pixels_per_unit),
+            top: Val::Px(height / 2. - hud_text.anchor.y * pixels_per_unit),
+            ..Default::default()
+        };
+
+        for section in text.sections.iter_mut() {
+            section.style.font_size = HUD_FONT_HEIGHT * pixels_per_unit;
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn display_hud_texts(
+    theme: Res<Theme>,
+    rules: Res<MatchRules>,
+    game_score: Res<GameScore>,
+    survival: Res<SurvivalRun>,
+    practice: Res<TargetPractice>,
+    breakout: Res<BreakoutRun>,
+    training: Res<TrainingStats>,
+    balls_query: Query<(&Velocity, &Ball)>,
+    slots_query: Query<(&PlayerId, &BonusSlots)>,
+    shields_query: Query<&PlayerId, With<Shield>>,
+    magnets_query: Query<(&PlayerId, &Magnet)>,
+    mut texts_query: Query<(&mut Text, &HudText)>,
+) {
+    let rally = balls_query.iter().map(|(_, ball)| ball.touched_paddles).max().unwrap_or(0);
+    let speed = balls_query.iter().map(|(velocity, _)| velocity.linear.length()).fold(0., f32::max);
+
+    for (mut text, hud_text) in texts_query.iter_mut() {
+        let value = match hud_text.kind {
+            HudTextKind::Health(player) => {
+                let mut value = format!(
+                    "{}/{}",
+                    game_score.player(player).health,
+                    rules.handicap(player).health
+                );
+                // The bonuses kept by the paddles of the side, the next one used first
+                for (_, slots) in slots_query.iter().filter(|(p, _)| **p == player) {
+                    for bonus in slots.0.iter() {
+                        value.push(' ');
+                        value.push_str(bonus.name());
+                    }
+                }
+                if shields_query.iter().any(|p| *p == player) {
+                    value.push_str(" [Shield]");
+                }
+                if let Some((_, magnet)) = magnets_query.iter().find(|(p, _)| **p == player) {
+                    value.push_str(&format!(" [Magnet {}]", magnet.catches));
+                }
+                value
+            }
+            HudTextKind::Round if rules.mode == GameMode::Survival => {
+                format!("Wave {}  {:.0}s", survival.wave + 1, survival.elapsed)
+            }
+            HudTextKind::Round if rules.mode == GameMode::TargetPractice => format!(
+                "Targets {}/{}  {:.0}s",
+                practice.broken, practice.targets, practice.remaining
+            ),
+            HudTextKind::Round if rules.mode == GameMode::Breakout => {
+                format!("{}  Bricks {}/{}", breakout.level, breakout.broken, breakout.bricks)
+            }
+            HudTextKind::Round if rules.mode == GameMode::Training => {
+                rules.training.drill.name().to_string()
+            }
+            HudTextKind::Round => {
+                let max_rounds = rules.max_rounds(game_score.players.len());
+                format!("Round {}/{}", game_score.round().min(max_rounds), max_rounds)
+            }
+            HudTextKind::Rally if rules.mode == GameMode::TargetPractice => {
+                format!("Accuracy {:.0}%", practice.accuracy() * 100.)
+            }
+            HudTextKind::Rally if rules.mode == GameMode::Training => format!(
+                "Returned {}/{} ({:.0}%)",
+                training.returned,
+                training.returned + training.missed,
+                training.return_rate() * 100.
+            ),
+            HudTextKind::Rally => format!("Rally {}", rally),
+            HudTextKind::BallSpeed => format!("Speed {:.1}", speed),
+        };
+
+        update_text(&mut text, value, &theme);
+    }
+}
+
+/// Spawns a text in the top right corner of the window, the custom match
+/// screen uses it to show the results of the game modes.
+pub fn spawn_corner_text<T: Component + Default>(
+    mut commands: Commands,
+    assets: Res<FontAssets>,
+    theme: Res<Theme>,
+) {
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect { right: Val::Px(20.), top: Val::Px(20.), ..Default::default() },
+                ..Default::default()
+            },
+            text: Text::with_section(
+                "",
+                TextStyle {
+                    font: assets.monogram.clone(),
+                    font_size: 24.,
+                    color: theme.field_lines,
+                },
+                Default::default(),
+            ),
+            ..Default::default()
+        })
+        .insert(T::default());
+}
+
+/// Updates the value and the color of a text, only when they differ so that the UI
+/// is not laid out again every frame.
+pub fn update_text(text: &mut Text, value: String, theme: &Theme) {
+    let section = &mut text.sections[0];
+    if section.value != value {
+        section.value = value;
+    }
+    if section.style.color != theme.field_lines {
+        section.style.color = theme.field_lines;
+    }
+}
+
+pub fn despawn_with<T: Component>(mut commands: Commands, query: Query<Entity, With<T>>) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
@@ -1,151 +1,132 @@
 use bevy::prelude::*;
+use bevy::render::camera::ScalingMode;
 use bevy_kira_audio::Audio;
 use heron::prelude::*;
 
+use crate::arena::{Arena, ArenaRect};
 use crate::game_collisions::GamePhysicsLayer;
+use crate::hud::HUD_Z;
+use crate::inventory::BonusSlots;
+use crate::rules::MatchRules;
+use crate::theme::{spawn_paddle_cues, Theme};
 use crate::{
-    AudioAssets, Goal, HudAssets, Lifebar, LifebarAssets, Paddle, Round, Side, VersusAssets,
-    BLUE_COLOR, RED_COLOR, WHITE_COLOR,
+    AudioAssets, ComputerTarget, Controller, Edge, FieldLine, Goal, HudAssets, Lifebar,
+    LifebarAssets, Paddle, PlayerId, Round, Side, VersusAssets,
 };
 
-pub const PLAYER_PADDLE_HEIGHT: f32 = 5.;
-pub const COMPUTER_PADDLE_HEIGHT: f32 = 8.;
+/// Half of the visible height of the arena, in world units,
+/// the camera keeps it whatever the size of the window is.
+pub const VIEW_HALF_HEIGHT: f32 = 7.2;
 
 pub fn camera_setup(mut commands: Commands) {
     let mut camera_bundle = OrthographicCameraBundle::new_2d();
-    camera_bundle.orthographic_projection.scale = 1. / 50.;
+    camera_bundle.orthographic_projection.scaling_mode = ScalingMode::FixedVertical;
+    camera_bundle.orthographic_projection.scale = VIEW_HALF_HEIGHT;
     commands.spawn_bundle(camera_bundle);
+    commands.spawn_bundle(UiCameraBundle::default());
 }
 
-pub fn spawn_paddles(mut commands: Commands) {
-    // Player paddle (on the right)
-    commands
-        .spawn_bundle(SpriteBundle {
-            sprite: Sprite {
-                color: BLUE_COLOR,
-                custom_size: Some(Vec2::new(0.5, PLAYER_PADDLE_HEIGHT)),
-                ..Default::default()
-            },
-            transform: Transform::from_translation(Vec3::new(11., 0., 0.)),
-            ..Default::default()
-        })
-        .insert(Velocity::default())
-        .insert(RigidBody::KinematicPositionBased)
-        .insert(CollisionShape::Cuboid {
-            half_extends: Vec3::new(0.25, PLAYER_PADDLE_HEIGHT / 2., 0.),
-            border_radius: None,
-        })
-        .insert(RotationConstraints::lock())
-        .insert(PhysicMaterial {
-            restitution: PhysicMaterial::PERFECTLY_ELASTIC_RESTITUTION,
-            ..Default::default()
-        })
-        .insert(
-            CollisionLayers::none()
-                .with_group(GamePhysicsLayer::Paddle)
-                .with_masks(&[GamePhysicsLayer::Ball, GamePhysicsLayer::Edge]),
-        )
-        .insert(Paddle::Player);
+pub fn spawn_paddles(
+    mut commands: Commands,
+    theme: Res<Theme>,
+    rules: Res<MatchRules>,
+    arena: Res<Arena>,
+) {
+    for player in arena.player_ids().filter(|p| rules.plays(*p)) {
+        for (member, track) in
+            arena.paddle_tracks(player, rules.team_layout).into_iter().enumerate()
+        {
+            let height = rules.handicap(player).paddle_height;
+            let mut transform = Transform::from_translation(track.start.extend(0.));
+            transform.rotation = Quat::from_rotation_z(track.rotation);
+            let controller = match (player.is_human(), member) {
+                (true, _) if rules.computer_player => Controller::Computer,
+                (true, 0) => Controller::Human,
+                (true, _) if rules.human_teammate => Controller::SecondHuman,
+                (false, 0) if player == PlayerId(1) && rules.human_opponent => {
+                    Controller::SecondHuman
+                }
+                _ => Controller::Computer,
+            };
 
-    // Computer paddle (on the left)
-    commands
-        .spawn_bundle(SpriteBundle {
-            sprite: Sprite {
-                color: RED_COLOR,
-                custom_size: Some(Vec2::new(0.5, COMPUTER_PADDLE_HEIGHT)),
-                ..Default::default()
-            },
-            transform: Transform::from_translation(Vec3::new(-11., 0., 0.)),
-            ..Default::default()
-        })
-        .insert(Velocity::default())
-        .insert(RigidBody::KinematicPositionBased)
-        .insert(CollisionShape::Cuboid {
-            half_extends: Vec3::new(0.25, COMPUTER_PADDLE_HEIGHT / 2., 0.),
-            border_radius: None,
-        })
-        .insert(RotationConstraints::lock())
-        .insert(PhysicMaterial {
-            restitution: PhysicMaterial::PERFECTLY_ELASTIC_RESTITUTION,
-            ..Default::default()
-        })
-        .insert(
-            CollisionLayers::none()
-                .with_group(GamePhysicsLayer::Paddle)
-                .with_masks(&[GamePhysicsLayer::Ball, GamePhysicsLayer::Edge]),
-        )
-        .insert(Paddle::Computer);
+            commands
+                .spawn_bundle(SpriteBundle {
+                    sprite: Sprite {
+                        color: theme.paddle_color(player),
+                        custom_size: Some(Vec2::new(0.5, height)),
+                        ..Default::default()
+                    },
+                    transform,
+                    ..Default::default()
+                })
+                .insert(Velocity::default())
+                .insert(RigidBody::KinematicPositionBased)
+                .insert(CollisionShape::Cuboid {
+                    half_extends: Vec3::new(0.25, height / 2., 0.),
+                    border_radius: None,
+                })
+                .insert(RotationConstraints::lock())
+                .insert(PhysicMaterial {
+                    restitution: PhysicMaterial::PERFECTLY_ELASTIC_RESTITUTION,
+                    ..Default::default()
+                })
+                .insert(
+                    CollisionLayers::none()
+                        .with_group(GamePhysicsLayer::Paddle)
+                        .with_masks(&[GamePhysicsLayer::Ball, GamePhysicsLayer::Edge]),
+                )
+                .insert(Paddle)
+                .insert(player)
+                .insert(track)
+                .insert(controller)
+                .insert(BonusSlots::default())
+                .insert(ComputerTarget::default())
+                .with_children(|parent| spawn_paddle_cues(parent, player, &theme));
+        }
+    }
 }
 
-pub fn spawn_goals(mut commands: Commands) {
-    // Player goal (on the right)
-    commands
-        .spawn()
-        .insert(Transform::from_translation(Vec3::new(13., 0., 0.)))
-        .insert(GlobalTransform::default())
-        .insert(RigidBody::Sensor)
-        .insert(CollisionShape::Cuboid {
-            half_extends: Vec3::new(1.0, 10., 0.),
-            border_radius: None,
-        })
-        .insert(RotationConstraints::lock())
-        .insert(
-            CollisionLayers::none()
-                .with_group(GamePhysicsLayer::Goal)
-                .with_mask(GamePhysicsLayer::Ball),
-        )
-        .insert(Goal::Player);
-
-    // Computer goal (on the left)
-    commands
-        .spawn()
-        .insert(Transform::from_translation(Vec3::new(-13., 0., 0.)))
-        .insert(GlobalTransform::default())
-        .insert(RigidBody::Sensor)
-        .insert(CollisionShape::Cuboid {
-            half_extends: Vec3::new(1.0, 10., 0.),
-            border_radius: None,
-        })
-        .insert(RotationConstraints::lock())
-        .insert(
-            CollisionLayers::none()
-                .with_group(GamePhysicsLayer::Goal)
-                .with_mask(GamePhysicsLayer::Ball),
-        )
-        .insert(Goal::Computer);
+pub fn spawn_goals(mut commands: Commands, rules: Res<MatchRules>, arena: Res<Arena>) {
+    for player in arena.player_ids().filter(|p| rules.plays(*p) || rules.mode.has_open_goals()) {
+        let rect = arena.player(player).goal;
+        commands
+            .spawn()
+            .insert(rect.transform())
+            .insert(GlobalTransform::default())
+            .insert(RigidBody::Sensor)
+            .insert(CollisionShape::Cuboid {
+                half_extends: rect.half_extends(),
+                border_radius: None,
+            })
+            .insert(RotationConstraints::lock())
+            .insert(
+                CollisionLayers::none()
+                    .with_group(GamePhysicsLayer::Goal)
+                    .with_mask(GamePhysicsLayer::Ball),
+            )
+            .insert(Goal)
+            .insert(player);
+    }
 }
 
-pub fn spawn_edges(mut commands: Commands) {
-    // Top edge
-    commands
-        .spawn()
-        .insert(Transform::from_translation(Vec3::new(0., 7., 0.)))
-        .insert(GlobalTransform::default())
-        .insert(RigidBody::Static)
-        .insert(CollisionShape::Cuboid {
-            half_extends: Vec3::new(14., 1., 0.),
-            border_radius: None,
-        })
-        .insert(PhysicMaterial {
-            restitution: PhysicMaterial::PERFECTLY_ELASTIC_RESTITUTION,
-            ..Default::default()
-        })
-        .insert(
-            CollisionLayers::none()
-                .with_group(GamePhysicsLayer::Edge)
-                .with_masks(&[GamePhysicsLayer::Ball, GamePhysicsLayer::Paddle]),
-        );
+pub fn spawn_edges(mut commands: Commands, rules: Res<MatchRules>, arena: Res<Arena>) {
+    for rect in arena.edges.iter() {
+        spawn_edge(&mut commands, rect);
+    }
 
-    // Bottom edge
+    // The goals of the players who do not play are walled up
+    for player in arena.player_ids().filter(|p| !rules.plays(*p) && !rules.mode.has_open_goals()) {
+        spawn_edge(&mut commands, &arena.player(player).goal);
+    }
+}
+
+fn spawn_edge(commands: &mut Commands, rect: &ArenaRect) {
     commands
         .spawn()
-        .insert(Transform::from_translation(Vec3::new(0., -7., 0.)))
+        .insert(rect.transform())
         .insert(GlobalTransform::default())
         .insert(RigidBody::Static)
-        .insert(CollisionShape::Cuboid {
-            half_extends: Vec3::new(14., 1., 0.),
-            border_radius: None,
-        })
+        .insert(CollisionShape::Cuboid { half_extends: rect.half_extends(), border_radius: None })
         .insert(PhysicMaterial {
             restitution: PhysicMaterial::PERFECTLY_ELASTIC_RESTITUTION,
             ..Default::default()
@@ -154,156 +135,124 @@ pub fn spawn_edges(mut commands: Commands) {
             CollisionLayers::none()
                 .with_group(GamePhysicsLayer::Edge)
                 .with_masks(&[GamePhysicsLayer::Ball, GamePhysicsLayer::Paddle]),
-        );
-}
-
-pub fn spawn_sides(mut commands: Commands) {
-    // Right side
-    commands
-        .spawn()
-        .insert(Transform::from_translation(Vec3::new(6., 0., 0.)))
-        .insert(GlobalTransform::default())
-        .insert(RigidBody::Sensor)
-        .insert(CollisionShape::Cuboid { half_extends: Vec3::new(6., 6., 0.), border_radius: None })
-        .insert(
-            CollisionLayers::none()
-                .with_group(GamePhysicsLayer::Side)
-                .with_mask(GamePhysicsLayer::Ball),
-        )
-        .insert(Side::Player);
-
-    // Left side
-    commands
-        .spawn()
-        .insert(Transform::from_translation(Vec3::new(-6., 0., 0.)))
-        .insert(GlobalTransform::default())
-        .insert(RigidBody::Sensor)
-        .insert(CollisionShape::Cuboid { half_extends: Vec3::new(6., 6., 0.), border_radius: None })
-        .insert(
-            CollisionLayers::none()
-                .with_group(GamePhysicsLayer::Side)
-                .with_mask(GamePhysicsLayer::Ball),
         )
-        .insert(Side::Computer);
+        .insert(Edge);
 }
 
-pub fn spawn_field_lines(mut commands: Commands) {
-    // Top horizontal line
-    commands.spawn_bundle(SpriteBundle {
-        sprite: Sprite {
-            color: WHITE_COLOR,
-            custom_size: Some(Vec2::new(22.5, 0.1)),
-            ..Default::default()
-        },
-        transform: Transform::from_translation(Vec3::new(0., 6., 0.)),
-        ..Default::default()
-    });
-
-    // Bottom horizontal line
-    commands.spawn_bundle(SpriteBundle {
-        sprite: Sprite {
-            color: WHITE_COLOR,
-            custom_size: Some(Vec2::new(22.5, 0.1)),
-            ..Default::default()
-        },
-        transform: Transform::from_translation(Vec3::new(0., -6., 0.)),
-        ..Default::default()
-    });
+pub fn spawn_sides(mut commands: Commands, arena: Res<Arena>) {
+    for player in arena.player_ids() {
+        let rect = arena.player(player).side;
+        commands
+            .spawn()
+            .insert(rect.transform())
+            .insert(GlobalTransform::default())
+            .insert(RigidBody::Sensor)
+            .insert(CollisionShape::Cuboid {
+                half_extends: rect.half_extends(),
+                border_radius: None,
+            })
+            .insert(
+                CollisionLayers::none()
+                    .with_group(GamePhysicsLayer::Side)
+                    .with_mask(GamePhysicsLayer::Ball),
+            )
+            .insert(Side)
+            .insert(player);
+    }
 }
 
-pub fn spawn_lifebars(mut commands: Commands, assets: Res<LifebarAssets>) {
-    // Computer life bar
-    commands
-        .spawn_bundle(SpriteSheetBundle {
-            texture_atlas: assets.texture_atlas.clone(),
-            transform: Transform::from_translation(Vec3::new(-7.5, 6.625, 0.)),
-            sprite: TextureAtlasSprite {
-                index: 15,
-                custom_size: Some(Vec2::new(6., 0.5)),
+pub fn spawn_field_lines(mut commands: Commands, theme: Res<Theme>, arena: Res<Arena>) {
+    for rect in arena.field_lines.iter() {
+        commands
+            .spawn_bundle(SpriteBundle {
+                sprite: Sprite {
+                    color: theme.field_lines,
+                    custom_size: Some(rect.size),
+                    ..Default::default()
+                },
+                transform: rect.transform(),
                 ..Default::default()
-            },
-            ..Default::default()
-        })
-        .insert(Lifebar::Computer);
-
-    // Player life bar
-    commands
-        .spawn_bundle(SpriteSheetBundle {
-            texture_atlas: assets.texture_atlas.clone(),
-            transform: Transform::from_translation(Vec3::new(7.5, 6.625, 0.)),
-            sprite: TextureAtlasSprite {
-                index: 15,
-                flip_x: true,
-                custom_size: Some(Vec2::new(6., 0.5)),
-                ..Default::default()
-            },
-            ..Default::default()
-        })
-        .insert(Lifebar::Player);
+            })
+            .insert(FieldLine);
+    }
 }
 
-pub fn spawn_empty_round_slots(mut commands: Commands, assets: Res<HudAssets>) {
-    // Computer round #1
-    commands
-        .spawn_bundle(SpriteSheetBundle {
-            texture_atlas: assets.texture_atlas.clone(),
-            transform: Transform::from_translation(Vec3::new(-4., 6.625, 0.)),
-            sprite: TextureAtlasSprite {
-                index: 3,
-                custom_size: Some(Vec2::new(0.5, 0.5)),
-                ..Default::default()
-            },
-            ..Default::default()
-        })
-        .insert(Round::ComputerFirst);
-
-    // Computer round #2
-    commands
-        .spawn_bundle(SpriteSheetBundle {
-            texture_atlas: assets.texture_atlas.clone(),
-            transform: Transform::from_translation(Vec3::new(-3.3, 6.625, 0.)),
-            sprite: TextureAtlasSprite {
-                index: 3,
-                custom_size: Some(Vec2::new(0.5, 0.5)),
+pub fn spawn_lifebars(
+    mut commands: Commands,
+    theme: Res<Theme>,
+    assets: Res<LifebarAssets>,
+    rules: Res<MatchRules>,
+    arena: Res<Arena>,
+) {
+    for player in arena.player_ids().filter(|p| rules.plays(*p)) {
+        let position = arena.player(player).hud_position;
+        commands
+            .spawn_bundle(SpriteSheetBundle {
+                texture_atlas: assets.texture_atlas.clone(),
+                transform: Transform::from_translation(position.extend(HUD_Z)),
+                sprite: TextureAtlasSprite {
+                    index: 15,
+                    color: theme.paddle_color(player),
+                    // The lifebars on the right empty toward the center
+                    flip_x: position.x > 0.,
+                    custom_size: Some(Vec2::new(6., 0.5)),
+                    ..Default::default()
+                },
                 ..Default::default()
-            },
-            ..Default::default()
-        })
-        .insert(Round::ComputerSecond);
+            })
+            .insert(Lifebar)
+            .insert(player);
+    }
+}
 
-    // Player round #1
-    commands
-        .spawn_bundle(SpriteSheetBundle {
-            texture_atlas: assets.texture_atlas.clone(),
-            transform: Transform::from_translation(Vec3::new(4., 6.625, 0.)),
-            sprite: TextureAtlasSprite {
-                index: 3,
-                custom_size: Some(Vec2::new(0.5, 0.5)),
-                ..Default::default()
-            },
-            ..Default::default()
-        })
-        .insert(Round::PlayerFirst);
+pub fn spawn_empty_round_slots(
+    mut commands: Commands,
+    theme: Res<Theme>,
+    assets: Res<HudAssets>,
+    rules: Res<MatchRules>,
+    arena: Res<Arena>,
+) {
+    if !rules.mode.has_opponents() {
+        return;
+    }
 
-    // Player round #2
-    commands
-        .spawn_bundle(SpriteSheetBundle {
-            texture_atlas: assets.texture_atlas.clone(),
-            transform: Transform::from_translation(Vec3::new(3.3, 6.625, 0.)),
-            sprite: TextureAtlasSprite {
-                index: 3,
-                custom_size: Some(Vec2::new(0.5, 0.5)),
-                ..Default::default()
-            },
-            ..Default::default()
-        })
-        .insert(Round::PlayerSecond);
+    // The slots of each player go from its lifebar toward the center
+    for player in arena.player_ids() {
+        let position = arena.player(player).hud_position;
+        let direction = position.x.signum();
+        for index in 0..rules.rounds_to_win {
+            let x = position.x - direction * (3.5 + 0.7 * index as f32);
+            commands
+                .spawn_bundle(SpriteSheetBundle {
+                    texture_atlas: assets.texture_atlas.clone(),
+                    transform: Transform::from_translation(Vec3::new(x, position.y, HUD_Z)),
+                    sprite: TextureAtlasSprite {
+                        index: 3,
+                        color: theme.paddle_color(player),
+                        custom_size: Some(Vec2::new(0.5, 0.5)),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                })
+                .insert(Round { player, index });
+        }
+    }
 }
 
-pub fn spawn_versus(mut commands: Commands, assets: Res<VersusAssets>) {
+pub fn spawn_versus(
+    mut commands: Commands,
+    assets: Res<VersusAssets>,
+    rules: Res<MatchRules>,
+    arena: Res<Arena>,
+) {
+    // The versus icon only makes sense between two players
+    if arena.players.len() != 2 || !rules.mode.has_opponents() {
+        return;
+    }
+
     commands.spawn_bundle(SpriteSheetBundle {
         texture_atlas: assets.texture_atlas.clone(),
-        transform: Transform::from_translation(Vec3::new(0., 6.1, 2.)),
+        transform: Transform::from_translation(Vec3::new(0., 6.1, HUD_Z)),
         sprite: TextureAtlasSprite {
             index: 0,
             custom_size: Some(Vec2::new(1.8, 1.8)),
@@ -0,0 +1,98 @@
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+use bevy_kira_audio::Audio;
+use ordered_float::OrderedFloat;
+
+use crate::game_collisions::{CollisionStatus, GameCollisionEvent};
+use crate::rules::MatchRules;
+use crate::{
+    action_just_pressed, taken_bonus, AudioAssets, Ball, BonusType, Caught, Controller, PlayerId,
+    TakenBonusEvent,
+};
+
+/// The bonuses a paddle took and keeps to use them later, the oldest one is used first.
+/// They stay empty unless the rules give the paddles some bonus slots.
+#[derive(Component, Default)]
+pub struct BonusSlots(pub VecDeque<BonusType>);
+
+/// The ball split by a bonus used by a paddle: the nearest one it touched last, or the nearest one.
+fn split_ball_target<'a>(
+    paddle: Entity,
+    position: Vec2,
+    balls: impl Iterator<Item = (Entity, &'a GlobalTransform, &'a Ball)>,
+) -> Option<Entity> {
+    balls
+        .min_by_key(|(_, transform, ball)| {
+            let distance = transform.translation.truncate().distance(position);
+            (ball.last_touched_paddle != Some(paddle), OrderedFloat(distance))
+        })
+        .map(|(entity, ..)| entity)
+}
+
+/// Uses the oldest bonus of the paddles of the humans when they press their key.
+#[allow(clippy::too_many_arguments)]
+pub fn use_player_bonuses(
+    rules: Res<MatchRules>,
+    keys: Res<Input<KeyCode>>,
+    mut taken_bonus_writer: EventWriter<TakenBonusEvent>,
+    mut paddles_query: Query<(Entity, &GlobalTransform, &PlayerId, &Controller, &mut BonusSlots)>,
+    balls_query: Query<(Entity, &GlobalTransform, &Ball)>,
+    caught_query: Query<&Caught>,
+    audio_assets: Res<AudioAssets>,
+    audio: Res<Audio>,
+) {
+    for (entity, transform, player, controller, mut slots) in paddles_query.iter_mut() {
+        // Releasing a caught ball comes first
+        if !action_just_pressed(&keys, &rules, *controller)
+            || caught_query.iter().any(|caught| caught.paddle == entity)
+        {
+            continue;
+        }
+
+        let position = transform.translation.truncate();
+        let ball = match split_ball_target(entity, position, balls_query.iter()) {
+            Some(ball) => ball,
+            // The split ball needs a ball, the bonus is kept until there is one
+            None => continue,
+        };
+
+        if let Some(bonus) = slots.0.pop_front() {
+            if let Some(event) = taken_bonus(bonus, ball, Some(*player)) {
+                taken_bonus_writer.send(event);
+                audio.play(audio_assets.powerup_gain.clone());
+            }
+        }
+    }
+}
+
+/// The computers use their oldest bonus right after returning a ball,
+/// when it is heading toward the opponents and the effects hurt them the most.
+pub fn use_computer_bonuses(
+    mut collision_events: EventReader<GameCollisionEvent>,
+    mut taken_bonus_writer: EventWriter<TakenBonusEvent>,
+    mut paddles_query: Query<(&PlayerId, &Controller, &mut BonusSlots)>,
+    audio_assets: Res<AudioAssets>,
+    audio: Res<Audio>,
+) {
+    use GameCollisionEvent::*;
+
+    for event in collision_events.iter() {
+        if let BallAndPaddle { status: CollisionStatus::Stopped, ball, paddle } = event {
+            if let Ok((player, Controller::Computer, mut slots)) = paddles_query.get_mut(*paddle) {
+                if let Some(bonus) = slots.0.pop_front() {
+                    if let Some(event) = taken_bonus(bonus, *ball, Some(*player)) {
+                        taken_bonus_writer.send(event);
+                        audio.play(audio_assets.powerup_gain.clone());
+                    }
+                }
+            }
+        }
+    }
+}
+
+pub fn reset_bonus_slots(mut paddles_query: Query<&mut BonusSlots>) {
+    for mut slots in paddles_query.iter_mut() {
+        slots.0.clear();
+    }
+}
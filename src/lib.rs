@@ -10,30 +10,54 @@ use heron::rapier_plugin::convert::IntoRapier;
 use heron::rapier_plugin::rapier2d::dynamics::RigidBodySet;
 use heron::rapier_plugin::RigidBodyHandle;
 use ordered_float::OrderedFloat;
-use rand::prelude::SliceRandom;
 use rand::Rng;
+use serde::{Deserialize, Serialize};
+#[cfg(target_arch = "wasm32")]
 use wasm_bindgen::prelude::*;
 
+use self::arena::*;
 use self::assets::*;
+use self::bonuses::*;
+use self::breakout::*;
+use self::campaign::*;
+use self::custom_match::*;
+use self::director::*;
+use self::editor::*;
 use self::game_collisions::*;
+use self::hud::*;
 use self::init::*;
-
+use self::inventory::*;
+use self::obstacles::*;
+use self::rules::*;
+use self::survival::*;
+use self::targets::*;
+use self::theme::*;
+use self::tournament::*;
+use self::training::*;
+
+mod arena;
 mod assets;
+mod bonuses;
+mod breakout;
+mod campaign;
+mod custom_match;
+mod director;
+mod editor;
 mod game_collisions;
+mod hud;
 mod init;
-
-const WHITE_COLOR: Color = Color::rgb(0.922, 0.922, 0.922);
-const BLUE_COLOR: Color = Color::rgb(0.706, 0.706, 1.);
-const RED_COLOR: Color = Color::rgb(1., 0.706, 0.706);
-
-const PADDLE_SPEED: f32 = 10.0;
-const MAX_HEALTH: usize = 15;
-
-const BALL_SPEED: f32 = 10.0;
-const BALL_MAX_SPEED: f32 = 100.0;
-const BALL_TOUCH_PADDLE_SPEED_UP: f32 = 0.025;
-const BALL_TOUCH_EDGE_SPEED_UP: f32 = 0.0125;
+mod inventory;
+mod obstacles;
+mod rules;
+mod survival;
+mod targets;
+mod theme;
+mod tournament;
+mod training;
+
+const LIFEBAR_FRAMES: usize = 16;
 const PADDLE_ROTATION: f32 = PI / 15.;
+const BALL_RADIUS: f32 = 0.25;
 
 // For wasm-pack to be happy...
 #[cfg(target_arch = "wasm32")]
@@ -42,6 +66,7 @@ pub fn wasm_main() {
     init();
 }
 
+#[allow(clippy::default_constructed_unit_structs)]
 pub fn init() {
     let mut app = App::new();
     AssetLoader::new(States::AssetLoading)
@@ -50,43 +75,149 @@ pub fn init() {
         .with_collection::<LifebarAssets>()
         .with_collection::<SpacebarAssets>()
         .with_collection::<HudAssets>()
+        .with_collection::<ObstacleAssets>()
         .with_collection::<VersusAssets>()
         .with_collection::<AudioAssets>()
-        .continue_to_state(States::InitGame)
+        .with_collection::<FontAssets>()
+        .with_collection::<ArenaAssets>()
+        .with_collection::<LevelAssets>()
+        .continue_to_state(States::MatchSetup)
         .build(&mut app);
 
-    app.add_event::<GameCollisionEvent>()
+    app.add_asset::<Arena>()
+        .init_asset_loader::<ArenaLoader>()
+        .add_asset::<ArenaIndex>()
+        .init_asset_loader::<ArenaIndexLoader>()
+        .add_asset::<BreakoutLevel>()
+        .init_asset_loader::<LevelLoader>()
+        .add_asset::<LevelIndex>()
+        .init_asset_loader::<LevelIndexLoader>()
+        .add_event::<GameCollisionEvent>()
         .add_event::<SpawnBonusEvent>()
         .add_event::<TakenBonusEvent>()
         .add_state(States::AssetLoading)
-        .insert_resource(ClearColor(Color::rgb(0.239, 0.239, 0.239)))
+        .insert_resource(ClearColor(Theme::default().background))
+        .insert_resource(Theme::default())
+        .insert_resource(MatchRules::default())
+        .insert_resource(Arena::default())
+        .insert_resource(CustomMatchSelection::default())
+        .insert_resource(ArenaEditor::default())
         .insert_resource(GameScore::default())
-        .insert_resource(BonusesTimers(vec![
-            (Timer::new(Duration::from_secs(10), true), BonusType::SplitBall),
-            (Timer::new(Duration::from_secs(20), true), BonusType::BallsVerticalGravity),
-            (Timer::new(Duration::from_secs(25), true), BonusType::BallSpeedInArea),
-            (Timer::new(Duration::from_secs(30), true), BonusType::ShrinkPaddleSize),
-            (Timer::new(Duration::from_secs(30), true), BonusType::IncreasePaddleSize),
-        ]))
+        .insert_resource(BallScale::default())
+        .insert_resource(SlowMotion::default())
+        .insert_resource(SurvivalRun::default())
+        .insert_resource(TargetPractice::default())
+        .insert_resource(TargetPracticeResults::default())
+        .insert_resource(BreakoutRun::default())
+        .insert_resource(BreakoutResults::default())
+        .insert_resource(TrainingStats::default())
+        .insert_resource(TrainingResults::default())
+        .insert_resource(Campaign::default())
+        .insert_resource(Tournament::default())
+        .insert_resource(CampaignProgress::load(CAMPAIGN_FILE).unwrap_or_default())
+        .insert_resource(Leaderboard::load(LEADERBOARD_FILE).unwrap_or_default())
+        .insert_resource(BonusDirector::default())
         .add_plugins(DefaultPlugins)
         .add_plugin(PhysicsPlugin::default())
         .add_plugin(AnimationPlugin::default())
         .add_plugin(AudioPlugin::default())
         .insert_resource(Gravity::from(Vec3::ZERO))
         .add_startup_system(camera_setup)
+        .add_system(switch_theme)
+        .add_system(apply_theme)
+        .add_system(layout_hud_texts)
+        .add_system(display_hud_texts)
         .add_system_set(
-            SystemSet::on_enter(States::InitGame)
+            SystemSet::on_exit(States::AssetLoading)
                 .with_system(generate_animations)
+                .with_system(run_loop_music),
+        )
+        .add_system_set(
+            SystemSet::on_enter(States::MatchSetup)
+                .with_system(despawn_arena)
+                .with_system(end_playtest)
+                .with_system(spawn_custom_match_screen)
+                .with_system(spawn_corner_text::<LeaderboardText>)
+                .with_system(spawn_corner_text::<TargetPracticeText>)
+                .with_system(spawn_corner_text::<BreakoutText>)
+                .with_system(spawn_corner_text::<TrainingText>),
+        )
+        .add_system_set(
+            SystemSet::on_update(States::MatchSetup)
+                .with_system(edit_custom_match)
+                .with_system(display_custom_match)
+                .with_system(display_leaderboard)
+                .with_system(display_target_practice_results)
+                .with_system(display_breakout_results)
+                .with_system(display_training_results)
+                .with_system(finish_campaign_match)
+                .with_system(finish_tournament_match),
+        )
+        .add_system_set(
+            SystemSet::on_exit(States::MatchSetup)
+                .with_system(despawn_custom_match_screen)
+                .with_system(despawn_with::<LeaderboardText>)
+                .with_system(despawn_with::<TargetPracticeText>)
+                .with_system(despawn_with::<BreakoutText>)
+                .with_system(despawn_with::<TrainingText>)
+                .with_system(load_selected_arena),
+        )
+        .add_system_set(SystemSet::on_enter(States::Campaign).with_system(spawn_campaign_screen))
+        .add_system_set(
+            SystemSet::on_update(States::Campaign)
+                .with_system(edit_campaign)
+                .with_system(display_campaign),
+        )
+        .add_system_set(
+            SystemSet::on_exit(States::Campaign)
+                .with_system(despawn_campaign_screen)
+                .with_system(load_selected_arena),
+        )
+        .add_system_set(
+            SystemSet::on_enter(States::Tournament).with_system(spawn_tournament_screen),
+        )
+        .add_system_set(
+            SystemSet::on_update(States::Tournament)
+                .with_system(edit_tournament)
+                .with_system(display_tournament),
+        )
+        .add_system_set(
+            SystemSet::on_exit(States::Tournament)
+                .with_system(despawn_tournament_screen)
+                .with_system(load_selected_arena),
+        )
+        .add_system_set(
+            SystemSet::on_enter(States::ArenaEditor)
+                .with_system(despawn_arena)
+                .with_system(end_playtest)
+                .with_system(spawn_editor_text),
+        )
+        .add_system_set(
+            SystemSet::on_update(States::ArenaEditor)
+                .with_system(edit_arena_with_mouse)
+                .with_system(edit_arena_with_keys)
+                .with_system(display_editor_shapes)
+                .with_system(display_editor_text),
+        )
+        .add_system_set(SystemSet::on_exit(States::ArenaEditor).with_system(despawn_editor))
+        .add_system_set(
+            SystemSet::on_enter(States::InitGame)
+                .with_system(reset_game_score)
+                .with_system(reset_survival_run)
                 .with_system(spawn_paddles)
                 .with_system(enable_spawned_paddle_ccd)
                 .with_system(spawn_goals)
                 .with_system(spawn_edges)
                 .with_system(spawn_sides)
                 .with_system(spawn_field_lines)
+                .with_system(spawn_obstacles)
+                .with_system(spawn_targets)
+                .with_system(spawn_bricks)
+                .with_system(spawn_ball_machine)
                 .with_system(spawn_lifebars)
                 .with_system(spawn_empty_round_slots)
                 .with_system(spawn_versus)
-                .with_system(run_loop_music)
+                .with_system(spawn_hud_texts)
                 .with_system(ready_to_wait_player),
         )
         .add_system_set(
@@ -96,12 +227,27 @@ pub fn init() {
                 .with_system(enable_spawned_balls_ccd)
                 .with_system(reset_bonuses)
                 .with_system(reset_owned_bonuses)
-                .with_system(randomize_bonuses)
+                .with_system(reset_bonus_slots)
+                .with_system(reset_shields)
+                .with_system(reset_magnets)
+                .with_system(reset_ball_scale)
+                .with_system(reset_reversed_controls)
+                .with_system(reset_frozen_paddles)
+                .with_system(reset_slow_motion)
+                .with_system(reset_portals)
+                .with_system(reset_lifebar_gains)
+                .with_system(reset_bonus_director)
+                .with_system(reset_bonus_telegraphs)
                 .with_system(reset_paddles_velocity)
                 .with_system(reset_paddle_transform)
                 .with_system(reset_paddle_sizes),
         )
-        .add_system_set(SystemSet::on_update(States::WaitingPlayer).with_system(launch_ball))
+        .add_system_set(
+            SystemSet::on_update(States::WaitingPlayer)
+                .with_system(launch_ball)
+                .with_system(stop_training)
+                .with_system(stop_playtest),
+        )
         .add_system_set(SystemSet::on_enter(States::InGame).with_system(hide_spacebar_animation))
         .add_system_set(
             SystemSet::on_update(States::InGame)
@@ -112,24 +258,62 @@ pub fn init() {
                 .with_system(tilt_paddle)
                 .with_system(speed_up_balls_with_touched_paddles)
                 .with_system(speed_up_balls_with_touched_edges)
+                .with_system(speed_up_balls_with_touched_bumpers)
+                .with_system(move_moving_walls)
                 .with_system(clamp_ball_speed)
                 .with_system(track_damaging_balls)
                 .with_system(track_scores)
+                .with_system(escalate_survival_waves)
+                .with_system(count_survival_returns)
+                .with_system(end_survival_run)
+                .with_system(break_targets)
+                .with_system(end_target_practice)
+                .with_system(damage_bricks)
+                .with_system(display_bricks)
+                .with_system(end_breakout)
+                .with_system(fire_ball_machine)
+                .with_system(count_training_returns)
+                .with_system(stop_training)
                 .with_system(track_balls_touching_paddles)
                 .with_system(track_balls_entering_side)
                 .with_system(blip_on_ball_collisions)
-                .with_system(tick_bonuses_timers)
+                .with_system(direct_bonus_spawns)
+                .with_system(hatch_bonus_telegraphs)
                 .with_system(spawn_bonuses)
                 .with_system(manage_taken_bonuses)
+                .with_system(use_player_bonuses)
+                .with_system(use_computer_bonuses)
                 .with_system(store_taken_bonuses_in_score)
                 .with_system(manage_split_ball_bonus)
                 .with_system(manage_ball_speed_on_area_bonus)
                 .with_system(manage_balls_vertical_gravity_bonus)
                 .with_system(manage_shrink_paddle_size_bonus)
                 .with_system(manage_increase_paddle_size_bonus)
+                .with_system(manage_shield_bonus)
+                .with_system(break_shields)
+                .with_system(manage_magnet_bonus)
+                .with_system(catch_balls_with_magnets)
+                .with_system(carry_caught_balls)
+                .with_system(manage_ghost_ball_bonus)
+                .with_system(fade_ghost_balls)
+                .with_system(manage_ball_scale_bonuses)
+                .with_system(scale_balls)
+                .with_system(manage_reverse_controls_bonus)
+                .with_system(expire_reversed_controls)
+                .with_system(manage_freeze_paddles_bonus)
+                .with_system(thaw_frozen_paddles)
+                .with_system(manage_slow_motion_bonus)
+                .with_system(update_slow_motion)
+                .with_system(manage_portals_bonus)
+                .with_system(teleport_balls)
+                .with_system(close_portals)
+                .with_system(manage_health_bonuses)
+                .with_system(animate_lifebar_gains)
                 .with_system(regame_when_no_balls)
                 .with_system(display_rounds)
-                .with_system(display_lifebars),
+                .with_system(display_lifebars)
+                .with_system(display_balls_colors)
+                .with_system(stop_playtest),
         )
         .run();
 }
@@ -176,21 +360,45 @@ fn ready_to_wait_player(mut state: ResMut<State<States>>) {
     state.set(States::WaitingPlayer).unwrap()
 }
 
+/// Despawns everything that was spawned for the previous match, only the cameras stay.
+fn despawn_arena(
+    mut commands: Commands,
+    entities_query: Query<Entity, (Without<Camera>, Without<Parent>)>,
+) {
+    for entity in entities_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+fn reset_game_score(mut game_score: ResMut<GameScore>, rules: Res<MatchRules>, arena: Res<Arena>) {
+    *game_score = GameScore::new(&rules, arena.players.len());
+}
+
 fn spawn_static_ball(mut commands: Commands, assets: Res<BallAssets>) {
+    spawn_ball(&mut commands, &assets, Transform::default(), Vec3::ZERO);
+}
+
+pub(crate) fn spawn_ball(
+    commands: &mut Commands,
+    assets: &BallAssets,
+    transform: Transform,
+    velocity: Vec3,
+) -> Entity {
     commands
         .spawn_bundle(SpriteSheetBundle {
             texture_atlas: assets.texture_atlas.clone(),
+            transform,
             sprite: TextureAtlasSprite {
                 index: 0,
-                custom_size: Some(Vec2::new(0.5, 0.5)),
+                custom_size: Some(Vec2::splat(BALL_RADIUS * 2.)),
                 ..Default::default()
             },
             ..Default::default()
         })
         .insert(Acceleration::default())
-        .insert(Velocity::default())
+        .insert(Velocity::from_linear(velocity))
         .insert(RigidBody::Dynamic)
-        .insert(CollisionShape::Sphere { radius: 0.25 })
+        .insert(CollisionShape::Sphere { radius: BALL_RADIUS })
         .insert(PhysicMaterial {
             restitution: PhysicMaterial::PERFECTLY_ELASTIC_RESTITUTION,
             ..Default::default()
@@ -201,8 +409,17 @@ fn spawn_static_ball(mut commands: Commands, assets: Res<BallAssets>) {
             GamePhysicsLayer::Edge,
             GamePhysicsLayer::Side,
             GamePhysicsLayer::Bonus,
+            GamePhysicsLayer::Obstacle,
+            GamePhysicsLayer::Bumper,
+            GamePhysicsLayer::MovingWall,
+            GamePhysicsLayer::Target,
+            GamePhysicsLayer::Brick,
+            GamePhysicsLayer::Shield,
+            GamePhysicsLayer::Portal,
         ]))
-        .insert(Ball::default());
+        .insert(Ball::default())
+        .with_children(spawn_ball_cue)
+        .id()
 }
 
 /// Enable the CCD to the spawned balls, things that can go fast.
@@ -229,20 +446,12 @@ fn enable_spawned_paddle_ccd(
     }
 }
 
-fn randomize_bonuses(mut bonuses_timers: ResMut<BonusesTimers>) {
-    let mut rng = rand::thread_rng();
-    bonuses_timers.0.shuffle(&mut rng);
-    bonuses_timers.0.iter_mut().for_each(|(timer, _)| {
-        *timer = Timer::from_seconds(rng.gen_range(8.0..40.0), true);
-    });
-}
-
-fn reset_paddle_sizes(mut paddles_query: Query<(&mut CollisionShape, &mut Sprite, &Paddle)>) {
-    for (col, mut sprite, paddle) in paddles_query.iter_mut() {
-        let default_size = match paddle {
-            Paddle::Player => PLAYER_PADDLE_HEIGHT,
-            Paddle::Computer => COMPUTER_PADDLE_HEIGHT,
-        };
+fn reset_paddle_sizes(
+    rules: Res<MatchRules>,
+    mut paddles_query: Query<(&mut CollisionShape, &mut Sprite, &PlayerId), With<Paddle>>,
+) {
+    for (col, mut sprite, player) in paddles_query.iter_mut() {
+        let default_size = rules.handicap(*player).paddle_height;
 
         if let CollisionShape::Cuboid { mut half_extends, .. } = col.into_inner() {
             half_extends[1] = default_size / 2.;
@@ -254,22 +463,10 @@ fn reset_paddle_sizes(mut paddles_query: Query<(&mut CollisionShape, &mut Sprite
     }
 }
 
-fn reset_paddle_transform(mut paddles_query: Query<&mut Transform, With<Paddle>>) {
-    for mut transform in paddles_query.iter_mut() {
-        transform.translation.y = 0.;
-        transform.rotation = Quat::IDENTITY;
-    }
-}
-
-fn tick_bonuses_timers(
-    time: Res<Time>,
-    mut bonuses_timers: ResMut<BonusesTimers>,
-    mut spawn_bonus_event: EventWriter<SpawnBonusEvent>,
-) {
-    for (timer, bonus) in bonuses_timers.0.iter_mut() {
-        if timer.tick(time.delta()).just_finished() {
-            spawn_bonus_event.send(SpawnBonusEvent(*bonus));
-        }
+fn reset_paddle_transform(mut paddles_query: Query<(&mut Transform, &PaddleTrack)>) {
+    for (mut transform, track) in paddles_query.iter_mut() {
+        transform.translation = track.start.extend(transform.translation.z);
+        transform.rotation = Quat::from_rotation_z(track.rotation);
     }
 }
 
@@ -280,8 +477,7 @@ fn reset_bonuses(mut command: Commands, bonuses_query: Query<Entity, With<BonusT
 }
 
 fn reset_owned_bonuses(mut game_score: ResMut<GameScore>) {
-    game_score.player_bonuses.clear();
-    game_score.computer_bonuses.clear();
+    game_score.players.iter_mut().for_each(|player| player.bonuses.clear());
 }
 
 // TODO we must also reset the translation, but we declared it as a
@@ -293,6 +489,9 @@ fn reset_paddles_velocity(mut paddles_query: Query<&mut Velocity, With<Paddle>>)
 }
 
 fn launch_ball(
+    rules: Res<MatchRules>,
+    arena: Res<Arena>,
+    survival: Res<SurvivalRun>,
     mut keys: ResMut<Input<KeyCode>>,
     mut state: ResMut<State<States>>,
     mut balls_query: Query<&mut Velocity, With<Ball>>,
@@ -300,78 +499,167 @@ fn launch_ball(
     if keys.clear_just_released(KeyCode::Space) {
         state.set(States::InGame).unwrap();
         let mut rng = rand::thread_rng();
+        let speed = rules.ball_speed * survival.speed_multiplier();
         for mut velocity in balls_query.iter_mut() {
-            let radian = rng.gen_range(0.0..PI / 2.) + 3. * PI / 4.;
-            let x = radian.cos() * BALL_SPEED;
-            let y = radian.sin() * BALL_SPEED;
-            velocity.linear = if rng.gen() { Vec3::new(x, y, 0.0) } else { Vec3::new(-x, y, 0.0) };
+            // The training serves toward the player like the ball machine does
+            if rules.mode == GameMode::Training {
+                velocity.linear = training_serve(&rules, &arena);
+                continue;
+            }
+
+            let direction = if rng.gen() { Vec2::X } else { -Vec2::X };
+            velocity.linear = serve_velocity(direction, rng.gen_range(-PI / 4.0..PI / 4.), speed);
         }
     }
 }
 
+/// The velocity of a served ball, its direction deviated by up to an eighth of a turn either way.
+fn serve_velocity(direction: Vec2, deviation: f32, speed: f32) -> Vec3 {
+    let deviation = deviation.clamp(-PI / 4., PI / 4.);
+    Quat::from_rotation_z(deviation).mul_vec3(direction.extend(0.)) * speed
+}
+
+/// Whether the action key of a human paddle was just pressed, to use a bonus or release a caught ball.
+fn action_just_pressed(keys: &Input<KeyCode>, rules: &MatchRules, controller: Controller) -> bool {
+    // The first human player keeps the space bar unless a second one needs the other side of the keyboard
+    match controller {
+        Controller::Human if rules.has_second_human() => keys.just_pressed(KeyCode::D),
+        Controller::Human => keys.any_just_pressed([KeyCode::Space, KeyCode::D]),
+        Controller::SecondHuman => keys.just_pressed(KeyCode::Left),
+        Controller::Computer => false,
+    }
+}
+
+#[allow(clippy::type_complexity)]
 fn move_player_paddle(
     time: Res<Time>,
+    rules: Res<MatchRules>,
     keys: Res<Input<KeyCode>>,
-    mut paddle_query: Query<(&mut Transform, &mut Velocity, &Paddle)>,
+    mut paddle_query: Query<
+        (&mut Transform, &mut Velocity, &PaddleTrack, &PlayerId, &Controller, Option<&Reversed>),
+        Without<Frozen>,
+    >,
 ) {
-    for (mut transform, mut velocity, paddle) in paddle_query.iter_mut() {
-        if let Paddle::Player = paddle {
-            let direction = if keys.any_pressed([KeyCode::Up, KeyCode::W]) {
-                1.
-            } else if keys.any_pressed([KeyCode::Down, KeyCode::S]) {
-                -1.
-            } else {
-                0.
-            };
-
-            velocity.linear.y = direction * PADDLE_SPEED;
-            transform.translation.y += time.delta_seconds() * direction * PADDLE_SPEED;
-            transform.translation.y = transform.translation.y.clamp(-8., 8.);
-        }
+    // The first human player keeps the arrows unless a second one needs them
+    let (up_keys, down_keys) = if rules.has_second_human() {
+        (vec![KeyCode::W], vec![KeyCode::S])
+    } else {
+        (vec![KeyCode::Up, KeyCode::W], vec![KeyCode::Down, KeyCode::S])
+    };
+
+    for (mut transform, mut velocity, track, player, controller, reversed) in
+        paddle_query.iter_mut()
+    {
+        let (up, down) = match controller {
+            Controller::Human => (up_keys.clone(), down_keys.clone()),
+            Controller::SecondHuman => (vec![KeyCode::Up], vec![KeyCode::Down]),
+            Controller::Computer => continue,
+        };
+
+        let direction = if keys.any_pressed(up) {
+            1.
+        } else if keys.any_pressed(down) {
+            -1.
+        } else {
+            0.
+        };
+        let direction = if reversed.is_some() { -direction } else { direction };
+
+        let speed = rules.paddle_speed(*player);
+        velocity.linear = (track.axis * direction * speed).extend(0.);
+        let position = transform.translation.truncate()
+            + time.delta_seconds() * track.axis * direction * speed;
+        transform.translation = track.clamp(position).extend(transform.translation.z);
     }
 }
 
+/// Moves the computer paddles toward the nearest ball, as their personality allows.
+#[allow(clippy::type_complexity)]
 fn move_computer_paddle(
     time: Res<Time>,
-    mut paddle_query: Query<(&mut Transform, &GlobalTransform, &Paddle)>,
-    balls_query: Query<&GlobalTransform, With<Ball>>,
+    rules: Res<MatchRules>,
+    mut paddle_query: Query<
+        (
+            &mut ComputerTarget,
+            &mut Transform,
+            &GlobalTransform,
+            &PaddleTrack,
+            &PlayerId,
+            &Controller,
+            Option<&Reversed>,
+        ),
+        Without<Frozen>,
+    >,
+    // The balls caught by the magnets follow the paddles, there is no point chasing them
+    balls_query: Query<(&GlobalTransform, Option<&Ghost>), (With<Ball>, Without<Caught>)>,
 ) {
-    for (mut transform, global_transform, paddle) in paddle_query.iter_mut() {
-        if let Paddle::Computer = paddle {
-            let position = global_transform.translation;
-            if let Some(nearest_ball_transform) = balls_query
-                .iter()
-                .min_by_key(|t| OrderedFloat((t.translation[0] - position[0]).abs()))
-            {
-                let distance_y = nearest_ball_transform.translation[1] - position[1];
-                let speed = if distance_y < 1.0 && distance_y > -1.0 {
+    let ai = rules.ai;
+    let mut rng = rand::thread_rng();
+
+    for (mut target, mut transform, global_transform, track, player, controller, reversed) in
+        paddle_query.iter_mut()
+    {
+        if *controller == Controller::Computer {
+            let position = global_transform.translation.truncate();
+            let normal = track.axis.perp();
+
+            // The computer only looks again at the balls once its reaction time is elapsed
+            target.cooldown -= time.delta_seconds();
+            if target.cooldown <= 0. {
+                target.cooldown = ai.reaction_time;
+                // The nearest ball is the one closest to the line the paddle moves along
+                target.position = balls_query
+                    .iter()
+                    .map(|(t, ghost)| {
+                        let ball = t.translation.truncate();
+                        ghost.map_or(ball, |ghost| ghost.apparent_position(ball))
+                    })
+                    .min_by_key(|ball| OrderedFloat((*ball - position).dot(normal).abs()))
+                    .map(|ball| {
+                        let aim = if rng.gen() { ai.aim_offset } else { -ai.aim_offset };
+                        ball.dot(track.axis) + aim
+                    });
+            }
+
+            if let Some(target) = target.position {
+                let distance = target - position.dot(track.axis);
+                let speed = if distance.abs() < ai.dead_zone {
                     0.0
-                } else if distance_y < 0. {
-                    -PADDLE_SPEED
+                } else if distance < 0. {
+                    -rules.paddle_speed(*player)
                 } else {
-                    PADDLE_SPEED
+                    rules.paddle_speed(*player)
+                };
+                let speed = match reversed {
+                    Some(reversed) if !reversed.adapted => -speed,
+                    _ => speed,
                 };
 
-                transform.translation.y += time.delta_seconds() * speed;
-                transform.translation.y = transform.translation.y.clamp(-8., 8.);
+                let position =
+                    transform.translation.truncate() + time.delta_seconds() * track.axis * speed;
+                transform.translation = track.clamp(position).extend(transform.translation.z);
             }
         }
     }
 }
 
-fn tilt_paddle(mut paddle_query: Query<(&mut Transform, &Velocity), With<Paddle>>) {
-    for (mut transform, velocity) in paddle_query.iter_mut() {
-        if velocity.linear.y > 0.1 {
-            transform.rotation = Quat::from_rotation_z(2. * PI - PADDLE_ROTATION);
-        } else if velocity.linear.y < -0.1 {
-            transform.rotation = Quat::from_rotation_z(PADDLE_ROTATION);
+fn tilt_paddle(mut paddle_query: Query<(&mut Transform, &Velocity, &PaddleTrack)>) {
+    for (mut transform, velocity, track) in paddle_query.iter_mut() {
+        let rotation = Quat::from_rotation_z(track.rotation);
+        let speed = velocity.linear.truncate().dot(track.axis);
+        transform.rotation = if speed > 0.1 {
+            rotation * Quat::from_rotation_z(2. * PI - PADDLE_ROTATION)
+        } else if speed < -0.1 {
+            rotation * Quat::from_rotation_z(PADDLE_ROTATION)
         } else {
-            transform.rotation = Quat::IDENTITY;
-        }
+            rotation
+        };
     }
 }
 
 fn speed_up_balls_with_touched_paddles(
+    rules: Res<MatchRules>,
+    ball_scale: Res<BallScale>,
     mut collision_events: EventReader<GameCollisionEvent>,
     mut balls_query: Query<(&mut Velocity, &mut Ball)>,
 ) {
@@ -381,13 +669,15 @@ fn speed_up_balls_with_touched_paddles(
         if let BallAndPaddle { status: CollisionStatus::Stopped, ball, .. } = event {
             if let Ok((mut velocity, mut ball)) = balls_query.get_mut(*ball) {
                 ball.touched_paddles += 1;
-                velocity.linear *= 1. + BALL_TOUCH_PADDLE_SPEED_UP;
+                // The heavier the balls, the less the paddles speed them up
+                velocity.linear *= 1. + rules.ball_touch_paddle_speed_up / ball_scale.mass;
             }
         }
     }
 }
 
 fn speed_up_balls_with_touched_edges(
+    rules: Res<MatchRules>,
     mut collision_events: EventReader<GameCollisionEvent>,
     mut balls_query: Query<&mut Velocity, With<Ball>>,
 ) {
@@ -396,16 +686,17 @@ fn speed_up_balls_with_touched_edges(
     for event in collision_events.iter() {
         if let BallAndEdge { status: CollisionStatus::Stopped, ball, .. } = event {
             if let Ok(mut velocity) = balls_query.get_mut(*ball) {
-                velocity.linear *= 1. + BALL_TOUCH_EDGE_SPEED_UP;
+                velocity.linear *= 1. + rules.ball_touch_edge_speed_up;
             }
         }
     }
 }
 
-fn clamp_ball_speed(mut balls_query: Query<&mut Velocity, With<Ball>>) {
+fn clamp_ball_speed(rules: Res<MatchRules>, mut balls_query: Query<&mut Velocity, With<Ball>>) {
     for mut velocity in balls_query.iter_mut() {
         let length = velocity.linear.length();
-        let multiplier = if length > BALL_MAX_SPEED { BALL_MAX_SPEED / length } else { 1.0 };
+        let max_speed = rules.ball_max_speed;
+        let multiplier = if length > max_speed { max_speed / length } else { 1.0 };
         velocity.linear *= multiplier;
     }
 }
@@ -414,7 +705,8 @@ fn track_damaging_balls(
     mut commands: Commands,
     mut collision_events: EventReader<GameCollisionEvent>,
     mut score: ResMut<GameScore>,
-    goals_query: Query<&Goal>,
+    rules: Res<MatchRules>,
+    goals_query: Query<&PlayerId, With<Goal>>,
     audio_assets: Res<AudioAssets>,
     audio: Res<Audio>,
 ) {
@@ -422,14 +714,11 @@ fn track_damaging_balls(
 
     for event in collision_events.iter() {
         if let BallAndGoal { status: CollisionStatus::Started, ball, goal } = event {
-            if let Ok(goal) = goals_query.get(*goal) {
-                match goal {
-                    Goal::Player => {
-                        score.player_health = score.player_health.saturating_sub(1);
-                    }
-                    Goal::Computer => {
-                        score.computer_health = score.computer_health.saturating_sub(1);
-                    }
+            if let Ok(player) = goals_query.get(*goal) {
+                // The misses of a training are only counted in its statistics
+                if rules.mode != GameMode::Training {
+                    let player = score.player_mut(*player);
+                    player.health = player.health.saturating_sub(1);
                 }
 
                 audio.play(audio_assets.goal.clone());
@@ -441,41 +730,57 @@ fn track_damaging_balls(
 
 fn track_scores(
     mut commands: Commands,
+    mut state: ResMut<State<States>>,
     mut score: ResMut<GameScore>,
+    rules: Res<MatchRules>,
     ball_query: Query<Entity, With<Ball>>,
 ) {
-    if score.player_health == 0 {
-        score.computer_rounds += 1;
-        score.computer_health = MAX_HEALTH;
-        score.player_health = MAX_HEALTH;
-        ball_query.iter().for_each(|e| commands.entity(e).despawn());
+    // The modes without opponents have no rounds, they end on their own
+    if !rules.mode.has_opponents() {
+        return;
     }
 
-    if score.computer_health == 0 {
-        score.player_rounds += 1;
-        score.player_health = MAX_HEALTH;
-        score.computer_health = MAX_HEALTH;
-        ball_query.iter().for_each(|e| commands.entity(e).despawn());
+    // The round ends when a player has no health left,
+    // it is won by the one with the most health left
+    if let Some(loser) = score.players.iter().position(|p| p.health == 0) {
+        let winner = score
+            .players
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| *i != loser)
+            .max_by_key(|(_, p)| p.health)
+            .map(|(i, _)| i);
+
+        if let Some(winner) = winner {
+            score.players[winner].rounds += 1;
+        }
+
+        for (i, player) in score.players.iter_mut().enumerate() {
+            player.health = rules.handicap(PlayerId(i)).health;
+        }
+        ball_query.iter().for_each(|e| commands.entity(e).despawn_recursive());
+    }
+
+    if score.is_changed() && score.winner(&rules).is_some() {
+        state.set(States::MatchSetup).unwrap();
     }
 }
 
+/// Credits the team of the paddle that touched the ball last.
 fn track_balls_touching_paddles(
     mut collision_events: EventReader<GameCollisionEvent>,
-    mut balls_query: Query<(&mut TextureAtlasSprite, &mut Ball)>,
-    paddles_query: Query<&Paddle>,
+    mut balls_query: Query<&mut Ball>,
+    paddles_query: Query<&PlayerId, With<Paddle>>,
 ) {
     use GameCollisionEvent::*;
 
     for event in collision_events.iter() {
         if let BallAndPaddle { status: CollisionStatus::Stopped, ball, paddle } = event {
-            if let Ok((mut texture_atlas_sprite, mut ball)) = balls_query.get_mut(*ball) {
+            if let (Ok(mut ball), Ok(player)) =
+                (balls_query.get_mut(*ball), paddles_query.get(*paddle))
+            {
+                ball.last_touched_team = Some(*player);
                 ball.last_touched_paddle = Some(*paddle);
-                if let Ok(paddle) = paddles_query.get(*paddle) {
-                    match paddle {
-                        Paddle::Player => texture_atlas_sprite.color = BLUE_COLOR,
-                        Paddle::Computer => texture_atlas_sprite.color = RED_COLOR,
-                    }
-                }
             }
         }
     }
@@ -483,7 +788,7 @@ fn track_balls_touching_paddles(
 
 fn track_balls_entering_side(
     mut collision_events: EventReader<GameCollisionEvent>,
-    sides_query: Query<&Side>,
+    sides_query: Query<&PlayerId, With<Side>>,
     mut balls_query: Query<&mut Ball>,
 ) {
     use CollisionStatus::*;
@@ -515,11 +820,27 @@ fn blip_on_ball_collisions(
         if matches!(event, BallAndPaddle { .. }) {
             audio.play(audio_assets.hit_1.clone());
         }
+
+        if matches!(
+            event,
+            BallAndObstacle { status: CollisionStatus::Started, .. }
+                | BallAndBumper { status: CollisionStatus::Started, .. }
+                | BallAndMovingWall { status: CollisionStatus::Started, .. }
+                | BallAndTarget { status: CollisionStatus::Started, .. }
+                | BallAndBrick { status: CollisionStatus::Started, .. }
+        ) {
+            audio.play(audio_assets.hit_0.clone());
+        }
     }
 }
 
-fn regame_when_no_balls(mut state: ResMut<State<States>>, balls_query: Query<(), With<Ball>>) {
-    if balls_query.is_empty() {
+fn regame_when_no_balls(
+    rules: Res<MatchRules>,
+    mut state: ResMut<State<States>>,
+    balls_query: Query<(), With<Ball>>,
+) {
+    // The ball machine keeps the training going
+    if balls_query.is_empty() && rules.mode != GameMode::Training {
         state.set(States::WaitingPlayer).unwrap();
     }
 }
@@ -527,14 +848,13 @@ fn regame_when_no_balls(mut state: ResMut<State<States>>, balls_query: Query<(),
 fn spawn_bonuses(
     mut commands: Commands,
     mut spawn_bonus_event: EventReader<SpawnBonusEvent>,
+    theme: Res<Theme>,
     bonuses_assets: Res<BonusesAssets>,
     audio_assets: Res<AudioAssets>,
     audio: Res<Audio>,
 ) {
-    let mut rng = rand::thread_rng();
-    for SpawnBonusEvent(bonus) in spawn_bonus_event.iter() {
-        let x = rng.gen_range(-10.0..10.0);
-        let y = rng.gen_range(-5.5..5.5);
+    for SpawnBonusEvent { bonus, position } in spawn_bonus_event.iter() {
+        let (x, y) = (position.x, position.y);
 
         let (texture_atlas, index) = match bonus {
             BonusType::SplitBall => (bonuses_assets.texture_atlas.clone(), 0),
@@ -542,19 +862,34 @@ fn spawn_bonuses(
             BonusType::BallsVerticalGravity => (bonuses_assets.texture_atlas.clone(), 4),
             BonusType::ShrinkPaddleSize => (bonuses_assets.paddle_texture_atlas.clone(), 1),
             BonusType::IncreasePaddleSize => (bonuses_assets.paddle_texture_atlas.clone(), 0),
+            BonusType::Shield => (bonuses_assets.shield_texture_atlas.clone(), 0),
+            BonusType::Magnet => (bonuses_assets.magnet_texture_atlas.clone(), 0),
+            BonusType::GhostBall => (bonuses_assets.ghost_texture_atlas.clone(), 0),
+            BonusType::BigBalls => (bonuses_assets.ball_texture_atlas.clone(), 0),
+            BonusType::SmallBalls => (bonuses_assets.ball_texture_atlas.clone(), 1),
+            BonusType::HeavyBalls => (bonuses_assets.ball_texture_atlas.clone(), 2),
+            BonusType::LightBalls => (bonuses_assets.ball_texture_atlas.clone(), 3),
+            BonusType::ReverseControls => (bonuses_assets.reverse_texture_atlas.clone(), 0),
+            BonusType::FreezePaddles => (bonuses_assets.time_texture_atlas.clone(), 0),
+            BonusType::SlowMotion => (bonuses_assets.time_texture_atlas.clone(), 1),
+            BonusType::Portals => (bonuses_assets.portal_texture_atlas.clone(), 0),
+            BonusType::ExtraLife => (bonuses_assets.health_texture_atlas.clone(), 0),
+            BonusType::StealHealth => (bonuses_assets.health_texture_atlas.clone(), 1),
         };
 
-        let mut commands = commands.spawn_bundle(SpriteSheetBundle {
-            texture_atlas: texture_atlas,
-            transform: Transform::from_translation(Vec3::new(x, y, 0.0)),
-            sprite: TextureAtlasSprite {
-                index,
-                custom_size: Some(Vec2::new(0.75, 0.75)),
-                ..Default::default()
-            },
-            ..Default::default()
-        });
         commands
+            .spawn_bundle(SpriteSheetBundle {
+                texture_atlas,
+                transform: Transform::from_translation(Vec3::new(x, y, 0.0)),
+                sprite: TextureAtlasSprite {
+                    index,
+                    color: theme.bonuses,
+                    custom_size: Some(Vec2::new(0.75, 0.75)),
+                    ..Default::default()
+                },
+                ..Default::default()
+            })
+            .insert(*bonus)
             .insert(Velocity::default())
             .insert(RigidBody::Sensor)
             .insert(CollisionShape::Cuboid {
@@ -567,35 +902,95 @@ fn spawn_bonuses(
                     .with_masks(&[GamePhysicsLayer::Ball, GamePhysicsLayer::Edge]),
             );
 
-        match bonus {
-            BonusType::SplitBall => {
-                commands.insert(BonusType::SplitBall);
-            }
-            BonusType::BallSpeedInArea => {
-                commands.insert(BonusType::BallSpeedInArea);
-            }
-            BonusType::BallsVerticalGravity => {
-                commands.insert(BonusType::BallsVerticalGravity);
-            }
-            BonusType::ShrinkPaddleSize => {
-                commands.insert(BonusType::ShrinkPaddleSize);
-            }
-            BonusType::IncreasePaddleSize => {
-                commands.insert(BonusType::IncreasePaddleSize);
-            }
-        }
-
         audio.play(audio_assets.powerup_spawn.clone());
     }
 }
 
+/// The effect of a bonus taken or used by a player, only the split ball needs no player.
+fn taken_bonus(
+    bonus: BonusType,
+    ball: Entity,
+    player: Option<PlayerId>,
+) -> Option<TakenBonusEvent> {
+    match (bonus, player) {
+        (BonusType::SplitBall, _) => {
+            Some(TakenBonusEvent { bonus: Bonus::SplitBall { ball }, player: None })
+        }
+        // The size and the weight of the balls change for everyone
+        (BonusType::BigBalls, _) => {
+            Some(TakenBonusEvent { bonus: Bonus::BallScale { size: 1.5, mass: 1. }, player: None })
+        }
+        (BonusType::SmallBalls, _) => Some(TakenBonusEvent {
+            bonus: Bonus::BallScale { size: 1. / 1.5, mass: 1. },
+            player: None,
+        }),
+        (BonusType::HeavyBalls, _) => {
+            Some(TakenBonusEvent { bonus: Bonus::BallScale { size: 1., mass: 2. }, player: None })
+        }
+        (BonusType::SlowMotion, _) => {
+            Some(TakenBonusEvent { bonus: Bonus::SlowMotion, player: None })
+        }
+        (BonusType::Portals, _) => Some(TakenBonusEvent { bonus: Bonus::Portals, player: None }),
+        (BonusType::LightBalls, _) => {
+            Some(TakenBonusEvent { bonus: Bonus::BallScale { size: 1., mass: 0.5 }, player: None })
+        }
+        (BonusType::BallSpeedInArea, Some(player)) => Some(TakenBonusEvent {
+            bonus: Bonus::BallSpeedInArea { benefiting_player: player },
+            player: Some(player),
+        }),
+        (BonusType::BallsVerticalGravity, Some(player)) => Some(TakenBonusEvent {
+            bonus: Bonus::BallsVerticalGravity { benefiting_player: player },
+            player: Some(player),
+        }),
+        (BonusType::ShrinkPaddleSize, Some(player)) => Some(TakenBonusEvent {
+            bonus: Bonus::ShrinkPaddleSize { benefiting_player: player },
+            player: Some(player),
+        }),
+        (BonusType::IncreasePaddleSize, Some(player)) => Some(TakenBonusEvent {
+            bonus: Bonus::IncreasePaddleSize { benefiting_player: player },
+            player: Some(player),
+        }),
+        (BonusType::Shield, Some(player)) => Some(TakenBonusEvent {
+            bonus: Bonus::Shield { benefiting_player: player },
+            player: Some(player),
+        }),
+        (BonusType::Magnet, Some(player)) => Some(TakenBonusEvent {
+            bonus: Bonus::Magnet { benefiting_player: player },
+            player: Some(player),
+        }),
+        (BonusType::GhostBall, Some(player)) => Some(TakenBonusEvent {
+            bonus: Bonus::GhostBall { benefiting_player: player },
+            player: Some(player),
+        }),
+        (BonusType::ReverseControls, Some(player)) => Some(TakenBonusEvent {
+            bonus: Bonus::ReverseControls { benefiting_player: player },
+            player: Some(player),
+        }),
+        (BonusType::FreezePaddles, Some(player)) => Some(TakenBonusEvent {
+            bonus: Bonus::FreezePaddles { benefiting_player: player },
+            player: Some(player),
+        }),
+        (BonusType::ExtraLife, Some(player)) => Some(TakenBonusEvent {
+            bonus: Bonus::ExtraLife { benefiting_player: player },
+            player: Some(player),
+        }),
+        (BonusType::StealHealth, Some(player)) => Some(TakenBonusEvent {
+            bonus: Bonus::StealHealth { benefiting_player: player },
+            player: Some(player),
+        }),
+        _ => None,
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 fn manage_taken_bonuses(
     mut commands: Commands,
+    rules: Res<MatchRules>,
     mut collision_events_reader: EventReader<GameCollisionEvent>,
     mut taken_bonus_writer: EventWriter<TakenBonusEvent>,
     balls_query: Query<&Ball>,
     bonuses_query: Query<&BonusType>,
-    paddles_query: Query<&Paddle>,
+    mut slots_query: Query<&mut BonusSlots>,
     audio_assets: Res<AudioAssets>,
     audio: Res<Audio>,
 ) {
@@ -604,42 +999,26 @@ fn manage_taken_bonuses(
 
     for event in collision_events_reader.iter() {
         if let BallAndBonus { status: Started, ball: ball_entity, bonus: bonus_entity } = event {
-            if let Ok(ball) = balls_query.get(*ball_entity) {
-                let paddle = match ball.last_touched_paddle {
-                    Some(paddle_entity) => paddles_query.get(paddle_entity).ok().copied(),
-                    None => None,
-                };
-
-                if let Ok(&bonus) = bonuses_query.get(*bonus_entity) {
-                    let bonus = match (bonus, paddle) {
-                        (BonusType::SplitBall, _) => Some(TakenBonusEvent {
-                            bonus: Bonus::SplitBall { ball: *ball_entity },
-                            paddle: None,
-                        }),
-                        (BonusType::BallSpeedInArea, Some(paddle)) => Some(TakenBonusEvent {
-                            bonus: Bonus::BallSpeedInArea { benefiting_paddle: paddle },
-                            paddle: Some(paddle),
-                        }),
-                        (BonusType::BallsVerticalGravity, Some(paddle)) => Some(TakenBonusEvent {
-                            bonus: Bonus::BallsVerticalGravity { benefiting_paddle: paddle },
-                            paddle: Some(paddle),
-                        }),
-                        (BonusType::ShrinkPaddleSize, Some(paddle)) => Some(TakenBonusEvent {
-                            bonus: Bonus::ShrinkPaddleSize { impacted_paddle: paddle.reverse() },
-                            paddle: Some(paddle),
-                        }),
-                        (BonusType::IncreasePaddleSize, Some(paddle)) => Some(TakenBonusEvent {
-                            bonus: Bonus::IncreasePaddleSize { benefiting_paddle: paddle },
-                            paddle: Some(paddle),
-                        }),
-                        _ => None,
-                    };
-
-                    if let Some(bonus) = bonus {
-                        taken_bonus_writer.send(bonus);
+            if let (Ok(ball), Ok(&bonus)) =
+                (balls_query.get(*ball_entity), bonuses_query.get(*bonus_entity))
+            {
+                // With bonus slots, the paddle that touched the ball last keeps the bonus for later,
+                // it stays on the field while the slots are full
+                let slots = ball.last_touched_paddle.and_then(|p| slots_query.get_mut(p).ok());
+                if let (true, Some(mut slots)) = (rules.bonus_slots > 0, slots) {
+                    if slots.0.len() < rules.bonus_slots {
+                        slots.0.push_back(bonus);
                         commands.entity(*bonus_entity).despawn_recursive();
                         audio.play(audio_assets.powerup_gain.clone());
                     }
+                    continue;
+                }
+
+                // The bonuses benefit the whole team that touched the ball last
+                if let Some(bonus) = taken_bonus(bonus, *ball_entity, ball.last_touched_team) {
+                    taken_bonus_writer.send(bonus);
+                    commands.entity(*bonus_entity).despawn_recursive();
+                    audio.play(audio_assets.powerup_gain.clone());
                 }
             }
         }
@@ -650,12 +1029,9 @@ fn store_taken_bonuses_in_score(
     mut score: ResMut<GameScore>,
     mut taken_bonus_reader: EventReader<TakenBonusEvent>,
 ) {
-    for TakenBonusEvent { bonus, paddle } in taken_bonus_reader.iter() {
-        if let Some(paddle) = paddle {
-            match paddle {
-                Paddle::Player => score.player_bonuses.push(*bonus),
-                Paddle::Computer => score.computer_bonuses.push(*bonus),
-            }
+    for TakenBonusEvent { bonus, player } in taken_bonus_reader.iter() {
+        if let Some(player) = player {
+            score.player_mut(*player).bonuses.push(*bonus);
         }
     }
 }
@@ -663,13 +1039,13 @@ fn store_taken_bonuses_in_score(
 fn manage_split_ball_bonus(
     mut commands: Commands,
     mut taken_bonus_reader: EventReader<TakenBonusEvent>,
-    balls_query: Query<(&Transform, &Velocity), With<Ball>>,
+    balls_query: Query<(&Transform, &Velocity, &CollisionShape, &TextureAtlasSprite), With<Ball>>,
     assets: Res<BallAssets>,
 ) {
     let mut rng = rand::thread_rng();
     for TakenBonusEvent { bonus, .. } in taken_bonus_reader.iter() {
         if let Bonus::SplitBall { ball } = bonus {
-            if let Ok((transform, velocity)) = balls_query.get(*ball) {
+            if let Ok((transform, velocity, shape, sprite)) = balls_query.get(*ball) {
                 // Rotate the velocity of the original ball by a random angle
                 let angle = rng.gen_range(0.0..2.0 * PI);
                 let x1 = velocity.linear[0];
@@ -678,34 +1054,9 @@ fn manage_split_ball_bonus(
                 let y2 = angle.sin() * x1 + angle.cos() * y1;
                 let velocity = Vec3::new(x2, y2, 0.);
 
-                commands
-                    .spawn_bundle(SpriteSheetBundle {
-                        texture_atlas: assets.texture_atlas.clone(),
-                        transform: *transform,
-                        sprite: TextureAtlasSprite {
-                            index: 0,
-                            custom_size: Some(Vec2::new(0.5, 0.5)),
-                            ..Default::default()
-                        },
-                        ..Default::default()
-                    })
-                    .insert(Acceleration::default())
-                    .insert(Velocity::from_linear(velocity))
-                    .insert(RigidBody::Dynamic)
-                    .insert(CollisionShape::Sphere { radius: 0.25 })
-                    .insert(PhysicMaterial {
-                        restitution: PhysicMaterial::PERFECTLY_ELASTIC_RESTITUTION,
-                        ..Default::default()
-                    })
-                    .insert(CollisionLayers::none().with_group(GamePhysicsLayer::Ball).with_masks(
-                        &[
-                            GamePhysicsLayer::Paddle,
-                            GamePhysicsLayer::Goal,
-                            GamePhysicsLayer::Edge,
-                            GamePhysicsLayer::Bonus,
-                        ],
-                    ))
-                    .insert(Ball::default());
+                // The new ball has the size of the original one
+                let ball = spawn_ball(&mut commands, &assets, *transform, velocity);
+                commands.entity(ball).insert(shape.clone()).insert(sprite.clone());
             }
         }
     }
@@ -716,75 +1067,50 @@ fn manage_ball_speed_on_area_bonus(
     mut collision_events_reader: EventReader<GameCollisionEvent>,
     game_score: Res<GameScore>,
     mut balls_query: Query<(&mut Velocity, &Ball)>,
-    side_query: Query<&Side>,
+    side_query: Query<&PlayerId, With<Side>>,
 ) {
     use Bonus::*;
     use CollisionStatus::*;
 
+    // The balls are sped up in a side by the bonuses of the other players
     fn change_velocity(
         velocity: &mut Velocity,
-        side: Side,
+        side: PlayerId,
         status: CollisionStatus,
-        player_speedups: usize,
-        computer_speedups: usize,
+        speedups: &[usize],
     ) {
-        match (side, status) {
-            (Side::Player, Started) => {
-                velocity.linear *= 1. + 1.5 * computer_speedups as f32;
-            }
-            (Side::Player, Stopped) => {
-                velocity.linear /= 1. + 1.5 * computer_speedups as f32;
-            }
-            (Side::Computer, Started) => {
-                velocity.linear *= 1. + 1.5 * player_speedups as f32;
-            }
-            (Side::Computer, Stopped) => {
-                velocity.linear /= 1. + 1.5 * player_speedups as f32;
-            }
+        let others_speedups: usize =
+            speedups.iter().enumerate().filter(|(i, _)| *i != side.0).map(|(_, s)| s).sum();
+        match status {
+            Started => velocity.linear *= 1. + 1.5 * others_speedups as f32,
+            Stopped => velocity.linear /= 1. + 1.5 * others_speedups as f32,
         }
     }
 
-    let player_speedups =
-        game_score.player_bonuses.iter().filter(|b| matches!(b, BallSpeedInArea { .. })).count();
-    let computer_speedups =
-        game_score.computer_bonuses.iter().filter(|b| matches!(b, BallSpeedInArea { .. })).count();
+    let speedups: Vec<usize> = game_score
+        .players
+        .iter()
+        .map(|p| p.bonuses.iter().filter(|b| matches!(b, BallSpeedInArea { .. })).count())
+        .collect();
 
     // We speed-up or slow-down the ball at the moment we take it
     for event in taken_bonus_reader.iter() {
-        if let TakenBonusEvent { bonus: BallSpeedInArea { benefiting_paddle }, .. } = event {
+        if let TakenBonusEvent { bonus: BallSpeedInArea { benefiting_player }, .. } = event {
             for (mut velocity, ball) in balls_query.iter_mut() {
                 if let Some(side) = ball.current_side {
-                    let status = match (benefiting_paddle, side) {
-                        (Paddle::Player, Side::Player) => Stopped,
-                        (Paddle::Computer, Side::Computer) => Stopped,
-                        (Paddle::Player, Side::Computer) => Started,
-                        (Paddle::Computer, Side::Player) => Started,
-                    };
-
-                    change_velocity(
-                        &mut velocity,
-                        side,
-                        status,
-                        player_speedups,
-                        computer_speedups,
-                    );
+                    let status = if *benefiting_player == side { Stopped } else { Started };
+                    change_velocity(&mut velocity, side, status, &speedups);
                 }
             }
         }
     }
 
-    if player_speedups > 0 || computer_speedups > 0 {
+    if speedups.iter().any(|s| *s > 0) {
         for event in collision_events_reader.iter() {
             if let GameCollisionEvent::BallAndSide { status, ball, side } = event {
                 if let Ok(side) = side_query.get(*side) {
                     if let Ok((mut velocity, _ball)) = balls_query.get_mut(*ball) {
-                        change_velocity(
-                            &mut velocity,
-                            *side,
-                            *status,
-                            player_speedups,
-                            computer_speedups,
-                        );
+                        change_velocity(&mut velocity, *side, *status, &speedups);
                     }
                 }
             }
@@ -793,16 +1119,16 @@ fn manage_ball_speed_on_area_bonus(
 }
 
 fn manage_balls_vertical_gravity_bonus(
+    arena: Res<Arena>,
     mut taken_bonus_reader: EventReader<TakenBonusEvent>,
     mut balls_query: Query<&mut Acceleration, With<Ball>>,
 ) {
     for TakenBonusEvent { bonus, .. } in taken_bonus_reader.iter() {
-        if let Bonus::BallsVerticalGravity { benefiting_paddle } = bonus {
+        if let Bonus::BallsVerticalGravity { benefiting_player } = bonus {
+            // The balls fall away from the goal of the benefiting player
+            let goal = arena.player(*benefiting_player).goal.position;
             for mut acceleration in balls_query.iter_mut() {
-                acceleration.linear = match benefiting_paddle {
-                    Paddle::Player => Vec3::new(-9.81, 0., 0.),
-                    Paddle::Computer => Vec3::new(9.81, 0., 0.),
-                };
+                acceleration.linear = (-goal.normalize_or_zero() * 9.81).extend(0.);
             }
         }
     }
@@ -810,12 +1136,13 @@ fn manage_balls_vertical_gravity_bonus(
 
 fn manage_shrink_paddle_size_bonus(
     mut taken_bonus_reader: EventReader<TakenBonusEvent>,
-    mut paddles_query: Query<(&mut CollisionShape, &mut Sprite, &Paddle)>,
+    mut paddles_query: Query<(&mut CollisionShape, &mut Sprite, &PlayerId), With<Paddle>>,
 ) {
     for TakenBonusEvent { bonus, .. } in taken_bonus_reader.iter() {
-        if let Bonus::ShrinkPaddleSize { impacted_paddle } = bonus {
-            for (col, mut sprite, paddle) in paddles_query.iter_mut() {
-                if impacted_paddle == paddle {
+        if let Bonus::ShrinkPaddleSize { benefiting_player } = bonus {
+            // Every opponent of the benefiting player is shrunk
+            for (col, mut sprite, player) in paddles_query.iter_mut() {
+                if benefiting_player != player {
                     if let CollisionShape::Cuboid { ref mut half_extends, .. } = col.into_inner() {
                         half_extends[1] = (half_extends[1] - 0.3).max(0.5);
                     }
@@ -830,12 +1157,12 @@ fn manage_shrink_paddle_size_bonus(
 
 fn manage_increase_paddle_size_bonus(
     mut taken_bonus_reader: EventReader<TakenBonusEvent>,
-    mut paddles_query: Query<(&mut CollisionShape, &mut Sprite, &Paddle)>,
+    mut paddles_query: Query<(&mut CollisionShape, &mut Sprite, &PlayerId), With<Paddle>>,
 ) {
     for TakenBonusEvent { bonus, .. } in taken_bonus_reader.iter() {
-        if let Bonus::IncreasePaddleSize { benefiting_paddle } = bonus {
-            for (col, mut sprite, paddle) in paddles_query.iter_mut() {
-                if benefiting_paddle == paddle {
+        if let Bonus::IncreasePaddleSize { benefiting_player } = bonus {
+            for (col, mut sprite, player) in paddles_query.iter_mut() {
+                if benefiting_player == player {
                     if let CollisionShape::Cuboid { ref mut half_extends, .. } = col.into_inner() {
                         half_extends[1] = (half_extends[1] + 0.3).min(5.);
                     }
@@ -853,135 +1180,281 @@ fn display_rounds(
     mut round_query: Query<(&mut TextureAtlasSprite, &Round)>,
 ) {
     for (mut texture_atlas_sprite, round) in round_query.iter_mut() {
-        texture_atlas_sprite.index = match round {
-            Round::ComputerFirst if game_score.computer_rounds > 0 => 2,
-            Round::ComputerSecond if game_score.computer_rounds > 1 => 2,
-            Round::PlayerFirst if game_score.player_rounds > 0 => 2,
-            Round::PlayerSecond if game_score.player_rounds > 1 => 2,
-            _ => 3,
-        };
+        let won_rounds = game_score.player(round.player).rounds;
+        texture_atlas_sprite.index = if won_rounds > round.index { 2 } else { 3 };
     }
 }
 
 fn display_lifebars(
     game_score: Res<GameScore>,
-    mut lifebar_query: Query<(&mut TextureAtlasSprite, &Lifebar)>,
+    rules: Res<MatchRules>,
+    mut lifebar_query: Query<(&mut TextureAtlasSprite, &PlayerId), With<Lifebar>>,
 ) {
-    for (mut texture_atlas_sprite, lifebar) in lifebar_query.iter_mut() {
-        texture_atlas_sprite.index = match lifebar {
-            Lifebar::Computer => game_score.computer_health,
-            Lifebar::Player => game_score.player_health,
-        };
+    for (mut texture_atlas_sprite, player) in lifebar_query.iter_mut() {
+        let health = game_score.player(*player).health;
+        texture_atlas_sprite.index = lifebar_index(health, rules.handicap(*player).health);
     }
 }
 
+/// Maps the health to one of the frames of the lifebar, rounding up
+/// so that the bar is never displayed empty while there is health left.
+fn lifebar_index(health: usize, max_health: usize) -> usize {
+    let last_frame = LIFEBAR_FRAMES - 1;
+    let max_health = max_health.max(1);
+    let health = health.min(max_health);
+    (health * last_frame).div_ceil(max_health)
+}
+
 struct GameScore {
-    computer_health: usize, // from 1 to 16
-    computer_rounds: usize, // from 0 to 2
-    computer_bonuses: Vec<Bonus>,
+    players: Vec<PlayerScore>, // indexed by the player ids
+}
 
-    player_health: usize, // from 1 to 16
-    player_rounds: usize, // from 0 to 2
-    player_bonuses: Vec<Bonus>,
+#[derive(Default)]
+struct PlayerScore {
+    health: usize, // from 0 to the handicap health
+    rounds: usize, // from 0 to rounds_to_win
+    bonuses: Vec<Bonus>,
+}
+
+impl GameScore {
+    fn new(rules: &MatchRules, players: usize) -> GameScore {
+        let players = (0..players)
+            .map(|i| PlayerScore {
+                health: rules.handicap(PlayerId(i)).health,
+                ..Default::default()
+            })
+            .collect();
+        GameScore { players }
+    }
+
+    fn player(&self, player: PlayerId) -> &PlayerScore {
+        &self.players[player.0]
+    }
+
+    fn player_mut(&mut self, player: PlayerId) -> &mut PlayerScore {
+        &mut self.players[player.0]
+    }
+
+    /// The number of the round being played, starting from one.
+    fn round(&self) -> usize {
+        self.players.iter().map(|p| p.rounds).sum::<usize>() + 1
+    }
+
+    /// The player that won the match, if any.
+    fn winner(&self, rules: &MatchRules) -> Option<PlayerId> {
+        self.players.iter().position(|p| p.rounds >= rules.rounds_to_win).map(PlayerId)
+    }
 }
 
 impl Default for GameScore {
     fn default() -> GameScore {
-        GameScore {
-            computer_health: MAX_HEALTH,
-            computer_rounds: 0,
-            computer_bonuses: Vec::new(),
-            player_health: MAX_HEALTH,
-            player_rounds: 0,
-            player_bonuses: Vec::new(),
-        }
+        GameScore::new(&MatchRules::default(), 2)
     }
 }
 
 #[derive(Clone, Eq, PartialEq, Debug, Hash)]
 enum States {
     AssetLoading,
+    MatchSetup,
+    Campaign,
+    Tournament,
+    ArenaEditor,
     InitGame,
     WaitingPlayer,
     InGame,
 }
 
-#[derive(Component, Debug, Copy, Clone, PartialEq, Eq)]
-enum Paddle {
-    Player,
-    Computer,
-}
+/// Identifies one of the players of the match, it is also the index of the player in the arena.
+/// The first player is the human one, the other ones are controlled by the computer.
+/// In doubles, a player is a team of paddles.
+#[derive(Component, Debug, Copy, Clone, PartialEq, Eq, Hash)]
+struct PlayerId(usize);
 
-impl Paddle {
-    fn reverse(&self) -> Paddle {
-        match self {
-            Paddle::Player => Paddle::Computer,
-            Paddle::Computer => Paddle::Player,
-        }
+impl PlayerId {
+    const HUMAN: PlayerId = PlayerId(0);
+
+    fn is_human(&self) -> bool {
+        *self == PlayerId::HUMAN
     }
 }
 
-#[derive(Component, Copy, Clone)]
-enum Goal {
-    Player,
-    Computer,
-}
+/// The paddle, goal, side and lifebar entities also have the `PlayerId` they belong to,
+/// the paddles of a team share the same one.
+#[derive(Component)]
+struct Paddle;
 
-#[derive(Component, Copy, Clone)]
-enum Side {
-    Player,
+/// Who moves a paddle.
+#[derive(Component, Debug, Copy, Clone, PartialEq, Eq)]
+enum Controller {
+    Human,
+    /// The teammate of the human player, with the arrow keys.
+    SecondHuman,
     Computer,
 }
 
+#[derive(Component)]
+struct Goal;
+
+#[derive(Component)]
+struct Side;
+
 #[derive(Default, Component)]
 struct Ball {
     touched_paddles: usize,
+    last_touched_team: Option<PlayerId>,
     last_touched_paddle: Option<Entity>,
-    current_side: Option<Side>,
+    current_side: Option<PlayerId>,
 }
 
-#[derive(Component)]
-struct BonusesTimers(Vec<(Timer, BonusType)>);
-
-struct SpawnBonusEvent(BonusType);
+struct SpawnBonusEvent {
+    bonus: BonusType,
+    position: Vec2,
+}
 
 #[derive(Debug)]
 struct TakenBonusEvent {
     bonus: Bonus,
-    paddle: Option<Paddle>,
+    player: Option<PlayerId>,
 }
 
 #[derive(Debug, Clone, Copy)]
 enum Bonus {
-    SplitBall { ball: Entity },
-    BallSpeedInArea { benefiting_paddle: Paddle },
-    BallsVerticalGravity { benefiting_paddle: Paddle },
-    ShrinkPaddleSize { impacted_paddle: Paddle },
-    IncreasePaddleSize { benefiting_paddle: Paddle },
+    SplitBall {
+        ball: Entity,
+    },
+    BallSpeedInArea {
+        benefiting_player: PlayerId,
+    },
+    BallsVerticalGravity {
+        benefiting_player: PlayerId,
+    },
+    ShrinkPaddleSize {
+        benefiting_player: PlayerId,
+    },
+    IncreasePaddleSize {
+        benefiting_player: PlayerId,
+    },
+    Shield {
+        benefiting_player: PlayerId,
+    },
+    Magnet {
+        benefiting_player: PlayerId,
+    },
+    GhostBall {
+        benefiting_player: PlayerId,
+    },
+    /// Multiplies the size and the mass of every ball.
+    BallScale {
+        size: f32,
+        mass: f32,
+    },
+    ReverseControls {
+        benefiting_player: PlayerId,
+    },
+    FreezePaddles {
+        benefiting_player: PlayerId,
+    },
+    SlowMotion,
+    Portals,
+    ExtraLife {
+        benefiting_player: PlayerId,
+    },
+    StealHealth {
+        benefiting_player: PlayerId,
+    },
 }
 
-#[derive(Component, Clone, Copy)]
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 enum BonusType {
     SplitBall,
     BallSpeedInArea,
     BallsVerticalGravity,
     ShrinkPaddleSize,
     IncreasePaddleSize,
+    Shield,
+    Magnet,
+    GhostBall,
+    BigBalls,
+    SmallBalls,
+    HeavyBalls,
+    LightBalls,
+    ReverseControls,
+    FreezePaddles,
+    SlowMotion,
+    Portals,
+    ExtraLife,
+    StealHealth,
 }
 
-#[derive(Component)]
-enum Lifebar {
-    Player,
-    Computer,
+impl BonusType {
+    const ALL: [BonusType; 18] = [
+        BonusType::SplitBall,
+        BonusType::BallSpeedInArea,
+        BonusType::BallsVerticalGravity,
+        BonusType::ShrinkPaddleSize,
+        BonusType::IncreasePaddleSize,
+        BonusType::Shield,
+        BonusType::Magnet,
+        BonusType::GhostBall,
+        BonusType::BigBalls,
+        BonusType::SmallBalls,
+        BonusType::HeavyBalls,
+        BonusType::LightBalls,
+        BonusType::ReverseControls,
+        BonusType::FreezePaddles,
+        BonusType::SlowMotion,
+        BonusType::Portals,
+        BonusType::ExtraLife,
+        BonusType::StealHealth,
+    ];
+
+    /// A short name, for the HUD.
+    fn name(&self) -> &'static str {
+        match self {
+            BonusType::SplitBall => "Split",
+            BonusType::BallSpeedInArea => "Speed",
+            BonusType::BallsVerticalGravity => "Gravity",
+            BonusType::ShrinkPaddleSize => "Shrink",
+            BonusType::IncreasePaddleSize => "Grow",
+            BonusType::Shield => "Shield",
+            BonusType::Magnet => "Magnet",
+            BonusType::GhostBall => "Ghost",
+            BonusType::BigBalls => "Big",
+            BonusType::SmallBalls => "Small",
+            BonusType::HeavyBalls => "Heavy",
+            BonusType::LightBalls => "Light",
+            BonusType::ReverseControls => "Reverse",
+            BonusType::FreezePaddles => "Freeze",
+            BonusType::SlowMotion => "Slow",
+            BonusType::Portals => "Portals",
+            BonusType::ExtraLife => "Life",
+            BonusType::StealHealth => "Steal",
+        }
+    }
 }
 
+/// Where a computer paddle is heading, until its reaction time lets it look at the balls again.
+#[derive(Component, Default)]
+struct ComputerTarget {
+    cooldown: f32,
+    position: Option<f32>,
+}
+
+#[derive(Component)]
+struct Lifebar;
+
 #[derive(Component)]
 struct SpacebarAnimation;
 
 #[derive(Component)]
-enum Round {
-    ComputerFirst,
-    ComputerSecond,
-    PlayerFirst,
-    PlayerSecond,
+struct FieldLine;
+
+#[derive(Component)]
+struct Edge;
+
+/// A slot displaying one of the rounds a paddle must win.
+#[derive(Component)]
+struct Round {
+    player: PlayerId,
+    index: usize,
 }
@@ -0,0 +1,206 @@
+use bevy::prelude::*;
+use heron::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::arena::{Arena, ArenaRect};
+use crate::game_collisions::{CollisionStatus, GameCollisionEvent, GamePhysicsLayer};
+use crate::theme::Theme;
+use crate::{Ball, ObstacleAssets};
+
+/// The description of an obstacle placed inside the arena.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ObstacleDef {
+    /// A static block the balls bounce on.
+    Block { position: Vec2, size: Vec2 },
+    /// A round bumper that speeds up the balls touching it.
+    Bumper { position: Vec2, radius: f32, speed_up: f32 },
+    /// A wall going back and forth along a path.
+    MovingWall { path: Vec<Vec2>, size: Vec2, speed: f32 },
+}
+
+impl ObstacleDef {
+    /// How far a point is from the obstacle, zero when inside.
+    /// The moving walls count the whole path they sweep.
+    pub fn distance(&self, point: Vec2) -> f32 {
+        match self {
+            ObstacleDef::Block { position, size } => {
+                ArenaRect { position: *position, size: *size, rotation: 0. }.distance(point)
+            }
+            ObstacleDef::Bumper { position, radius, .. } => {
+                (point.distance(*position) - radius).max(0.)
+            }
+            ObstacleDef::MovingWall { path, size, .. } => path
+                .iter()
+                .zip(path.iter().cycle().skip(1))
+                .map(|(from, to)| {
+                    // Measured from where the center of the wall passes nearest to the point
+                    let segment = *to - *from;
+                    let along = (point - *from).dot(segment) / segment.length_squared().max(1e-6);
+                    let position = *from + segment * along.clamp(0., 1.);
+                    ArenaRect { position, size: *size, rotation: 0. }.distance(point)
+                })
+                .fold(f32::INFINITY, f32::min),
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct Obstacle;
+
+#[derive(Component)]
+pub struct Bumper {
+    speed_up: f32,
+}
+
+#[derive(Component)]
+pub struct MovingWall {
+    path: Vec<Vec2>,
+    speed: f32,
+    next_point: usize,
+}
+
+pub fn spawn_obstacles(
+    mut commands: Commands,
+    arena: Res<Arena>,
+    theme: Res<Theme>,
+    obstacle_assets: Res<ObstacleAssets>,
+) {
+    for obstacle in arena.obstacles.iter() {
+        spawn_obstacle(&mut commands, obstacle, &theme, &obstacle_assets);
+    }
+}
+
+pub fn spawn_obstacle(
+    commands: &mut Commands,
+    obstacle: &ObstacleDef,
+    theme: &Theme,
+    obstacle_assets: &ObstacleAssets,
+) -> Entity {
+    let material = PhysicMaterial {
+        restitution: PhysicMaterial::PERFECTLY_ELASTIC_RESTITUTION,
+        ..Default::default()
+    };
+
+    match obstacle {
+        ObstacleDef::Block { position, size } => commands
+            .spawn_bundle(SpriteBundle {
+                sprite: Sprite {
+                    color: theme.obstacles,
+                    custom_size: Some(*size),
+                    ..Default::default()
+                },
+                transform: Transform::from_translation(position.extend(0.)),
+                ..Default::default()
+            })
+            .insert(RigidBody::Static)
+            .insert(CollisionShape::Cuboid {
+                half_extends: (*size / 2.).extend(0.),
+                border_radius: None,
+            })
+            .insert(material)
+            .insert(
+                CollisionLayers::none()
+                    .with_group(GamePhysicsLayer::Obstacle)
+                    .with_mask(GamePhysicsLayer::Ball),
+            )
+            .insert(Obstacle)
+            .id(),
+        ObstacleDef::Bumper { position, radius, speed_up } => commands
+            .spawn_bundle(SpriteSheetBundle {
+                texture_atlas: obstacle_assets.bumper_texture_atlas.clone(),
+                sprite: TextureAtlasSprite {
+                    index: 0,
+                    custom_size: Some(Vec2::splat(radius * 2.)),
+                    ..Default::default()
+                },
+                transform: Transform::from_translation(position.extend(0.)),
+                ..Default::default()
+            })
+            .insert(RigidBody::Static)
+            .insert(CollisionShape::Sphere { radius: *radius })
+            .insert(material)
+            .insert(
+                CollisionLayers::none()
+                    .with_group(GamePhysicsLayer::Bumper)
+                    .with_mask(GamePhysicsLayer::Ball),
+            )
+            .insert(Obstacle)
+            .insert(Bumper { speed_up: *speed_up })
+            .id(),
+        ObstacleDef::MovingWall { path, size, speed } => {
+            let start = path.first().copied().unwrap_or_default();
+            commands
+                .spawn_bundle(SpriteBundle {
+                    sprite: Sprite {
+                        color: theme.obstacles,
+                        custom_size: Some(*size),
+                        ..Default::default()
+                    },
+                    transform: Transform::from_translation(start.extend(0.)),
+                    ..Default::default()
+                })
+                .insert(Velocity::default())
+                .insert(RigidBody::KinematicPositionBased)
+                .insert(CollisionShape::Cuboid {
+                    half_extends: (*size / 2.).extend(0.),
+                    border_radius: None,
+                })
+                .insert(material)
+                .insert(
+                    CollisionLayers::none()
+                        .with_group(GamePhysicsLayer::MovingWall)
+                        .with_mask(GamePhysicsLayer::Ball),
+                )
+                .insert(Obstacle)
+                .insert(MovingWall { path: path.clone(), speed: *speed, next_point: 1 })
+                .id()
+        }
+    }
+}
+
+/// Moves the walls from one point of their path to the next one, looping when at the end.
+pub fn move_moving_walls(
+    time: Res<Time>,
+    mut walls_query: Query<(&mut Transform, &mut Velocity, &mut MovingWall)>,
+) {
+    for (mut transform, mut velocity, mut wall) in walls_query.iter_mut() {
+        if wall.path.len() < 2 {
+            continue;
+        }
+
+        let target = wall.path[wall.next_point % wall.path.len()];
+        let position = transform.translation.truncate();
+        let to_target = target - position;
+        let step = wall.speed * time.delta_seconds();
+
+        if to_target.length() <= step {
+            transform.translation = target.extend(transform.translation.z);
+            wall.next_point = (wall.next_point + 1) % wall.path.len();
+            // The wall already heads to the next point, the balls it hits bounce that way
+            let next_target = wall.path[wall.next_point];
+            velocity.linear = ((next_target - target).normalize_or_zero() * wall.speed).extend(0.);
+        } else {
+            let direction = to_target.normalize();
+            transform.translation += (direction * step).extend(0.);
+            velocity.linear = (direction * wall.speed).extend(0.);
+        }
+    }
+}
+
+pub fn speed_up_balls_with_touched_bumpers(
+    mut collision_events: EventReader<GameCollisionEvent>,
+    mut balls_query: Query<&mut Velocity, With<Ball>>,
+    bumpers_query: Query<&Bumper>,
+) {
+    use GameCollisionEvent::*;
+
+    for event in collision_events.iter() {
+        if let BallAndBumper { status: CollisionStatus::Stopped, ball, bumper } = event {
+            if let (Ok(mut velocity), Ok(bumper)) =
+                (balls_query.get_mut(*ball), bumpers_query.get(*bumper))
+            {
+                velocity.linear *= 1. + bumper.speed_up;
+            }
+        }
+    }
+}
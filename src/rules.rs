@@ -0,0 +1,494 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::{fmt, fs, io};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{BonusType, PlayerId};
+
+/// The file in which the custom match screen saves and loads the rules.
+pub const RULES_FILE: &str = "rules.ron";
+
+/// The rules of a match, every system reads them instead of constants.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MatchRules {
+    pub name: String,
+    pub mode: GameMode,
+    pub rounds_to_win: usize,
+    pub ball_speed: f32,
+    pub ball_max_speed: f32,
+    pub ball_touch_paddle_speed_up: f32,
+    pub ball_touch_edge_speed_up: f32,
+    pub paddle_speed: f32,
+    /// The range of seconds between two spawns of the same bonus, when its weight is one.
+    pub bonus_min_delay: f32,
+    pub bonus_max_delay: f32,
+    /// The number of bonuses a paddle can keep to use them when it wants,
+    /// the bonuses apply as soon as they are taken without any.
+    pub bonus_slots: usize,
+    /// The most bonuses that can wait on the field at the same time.
+    pub max_bonuses: usize,
+    /// The name of the arena the match is played in.
+    pub arena: String,
+    /// The name of the level of bricks of the breakout mode.
+    pub level: String,
+    pub team_layout: TeamLayout,
+    /// Whether the second paddle of the player's team is controlled
+    /// by a second human player instead of the computer.
+    pub human_teammate: bool,
+    /// Whether the first opponent is controlled by a second human player with the arrows.
+    pub human_opponent: bool,
+    /// Whether the paddles of the player are controlled by the computer, to watch computers play.
+    pub computer_player: bool,
+    pub player: Handicap,
+    pub computer: Handicap,
+    pub ai: AiPersonality,
+    /// The bonuses that can spawn during the match.
+    pub bonuses: Vec<BonusType>,
+    /// How often each bonus spawns compared to the others, the missing ones have a weight of one.
+    pub bonus_weights: HashMap<BonusType, f32>,
+    pub training: Training,
+}
+
+/// The settings specific to the human player or to the computers, tweaked to balance
+/// a match between players of different skills.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Handicap {
+    pub paddle_height: f32,
+    /// Multiplies the paddle speed of the rules.
+    pub paddle_speed: f32,
+    /// The health this side starts every round with, it is also its maximum.
+    pub health: usize,
+    /// Weights the chances that the bonuses spawn in this side's half of the field.
+    pub bonus_bias: f32,
+}
+
+impl Handicap {
+    pub fn new(paddle_height: f32, health: usize) -> Handicap {
+        Handicap { paddle_height, paddle_speed: 1., health, bonus_bias: 1. }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.health < 1 {
+            return Err(String::from("the health of a handicap must be at least 1"));
+        }
+        if !self.bonus_bias.is_finite() || self.bonus_bias < 0. {
+            return Err(String::from("the bonus bias of a handicap must be positive"));
+        }
+        if !self.paddle_height.is_finite() || self.paddle_height <= 0. {
+            return Err(String::from("the paddle height of a handicap must be above 0"));
+        }
+        if !self.paddle_speed.is_finite() || self.paddle_speed <= 0. {
+            return Err(String::from("the paddle speed of a handicap must be above 0"));
+        }
+        Ok(())
+    }
+}
+
+impl Default for Handicap {
+    fn default() -> Handicap {
+        Handicap::new(5., 15)
+    }
+}
+
+/// How the computers play.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AiPersonality {
+    /// How far from the center of the paddle the ball can be before the paddle moves.
+    pub dead_zone: f32,
+    /// The seconds the computers take to notice where the balls went.
+    pub reaction_time: f32,
+    /// How far from the center of their paddle the computers try to hit the balls,
+    /// to send them away with the tilted ends of the paddle.
+    pub aim_offset: f32,
+}
+
+impl Default for AiPersonality {
+    fn default() -> AiPersonality {
+        AiPersonality { dead_zone: 1., reaction_time: 0., aim_offset: 0. }
+    }
+}
+
+/// What the players play for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum GameMode {
+    /// Rounds against the computers.
+    #[default]
+    Versus,
+    /// Alone against endless waves of balls, until there is no health left.
+    Survival,
+    /// Alone against a wall of targets to break before the time is up.
+    TargetPractice,
+    /// Alone against the bricks of a level, they may drop bonuses when broken.
+    Breakout,
+    /// Alone against a ball machine firing the balls of a drill.
+    Training,
+}
+
+impl GameMode {
+    pub const ALL: [GameMode; 5] = [
+        GameMode::Versus,
+        GameMode::Survival,
+        GameMode::TargetPractice,
+        GameMode::Breakout,
+        GameMode::Training,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            GameMode::Versus => "Versus",
+            GameMode::Survival => "Survival",
+            GameMode::TargetPractice => "Target practice",
+            GameMode::Breakout => "Breakout",
+            GameMode::Training => "Training",
+        }
+    }
+
+    /// Whether the computers have paddles and goals, their goals are walls otherwise.
+    pub fn has_opponents(&self) -> bool {
+        matches!(self, GameMode::Versus)
+    }
+
+    /// Whether the goals of the computers stay open, even when they do not play.
+    pub fn has_open_goals(&self) -> bool {
+        matches!(self, GameMode::Versus | GameMode::Training)
+    }
+}
+
+/// The settings of the ball machine of the training mode.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Training {
+    pub drill: Drill,
+    /// The speed of the fired balls.
+    pub speed: f32,
+    /// The angle of the cone the balls are fired in, in degrees.
+    pub spread: f32,
+    /// The seconds between two shots.
+    pub interval: f32,
+}
+
+impl Default for Training {
+    fn default() -> Training {
+        Training::from(Drill::HighSpeed)
+    }
+}
+
+/// The drills of the training mode, each sets up the ball machine differently.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Drill {
+    /// Fast balls in a narrow cone.
+    #[default]
+    HighSpeed,
+    /// Several balls at once in a wide cone.
+    MultiBall,
+    /// Balls curved by a gravity toward the top or the bottom of the field.
+    Gravity,
+}
+
+impl Drill {
+    pub const ALL: [Drill; 3] = [Drill::HighSpeed, Drill::MultiBall, Drill::Gravity];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Drill::HighSpeed => "High speed returns",
+            Drill::MultiBall => "Multi-ball",
+            Drill::Gravity => "Gravity balls",
+        }
+    }
+
+    /// The number of balls fired at each shot.
+    pub fn balls(&self) -> usize {
+        match self {
+            Drill::MultiBall => 3,
+            _ => 1,
+        }
+    }
+
+    /// The acceleration curving the fired balls.
+    pub fn gravity(&self) -> f32 {
+        match self {
+            Drill::Gravity => 6.,
+            _ => 0.,
+        }
+    }
+}
+
+impl From<Drill> for Training {
+    fn from(drill: Drill) -> Training {
+        match drill {
+            Drill::HighSpeed => Training { drill, speed: 22., spread: 20., interval: 1.5 },
+            Drill::MultiBall => Training { drill, speed: 10., spread: 60., interval: 3. },
+            Drill::Gravity => Training { drill, speed: 10., spread: 30., interval: 2. },
+        }
+    }
+}
+
+/// How many paddles each side fields and where they stand,
+/// the paddles of a side share its goal and its health.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum TeamLayout {
+    #[default]
+    Single,
+    /// A paddle stands in front of the other one.
+    FrontBack,
+    /// Each paddle covers one half of the goal.
+    TopBottom,
+}
+
+impl TeamLayout {
+    pub const ALL: [TeamLayout; 3] =
+        [TeamLayout::Single, TeamLayout::FrontBack, TeamLayout::TopBottom];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            TeamLayout::Single => "Single",
+            TeamLayout::FrontBack => "Doubles front/back",
+            TeamLayout::TopBottom => "Doubles top/bottom",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RulesPreset {
+    Classic,
+    Chaos,
+    Marathon,
+    Survival,
+}
+
+impl RulesPreset {
+    pub const ALL: [RulesPreset; 4] =
+        [RulesPreset::Classic, RulesPreset::Chaos, RulesPreset::Marathon, RulesPreset::Survival];
+
+    pub fn next(&self) -> RulesPreset {
+        let index = RulesPreset::ALL.iter().position(|p| p == self).unwrap_or(0);
+        RulesPreset::ALL[(index + 1) % RulesPreset::ALL.len()]
+    }
+}
+
+impl From<RulesPreset> for MatchRules {
+    fn from(preset: RulesPreset) -> MatchRules {
+        let classic = MatchRules {
+            name: String::from("Classic"),
+            mode: GameMode::Versus,
+            rounds_to_win: 2,
+            ball_speed: 10.,
+            ball_max_speed: 100.,
+            ball_touch_paddle_speed_up: 0.025,
+            ball_touch_edge_speed_up: 0.0125,
+            paddle_speed: 10.,
+            bonus_min_delay: 8.,
+            bonus_max_delay: 40.,
+            bonus_slots: 0,
+            max_bonuses: 4,
+            arena: String::from("Classic"),
+            level: String::from("Wall"),
+            team_layout: TeamLayout::Single,
+            human_teammate: false,
+            human_opponent: false,
+            computer_player: false,
+            player: Handicap::new(5., 15),
+            computer: Handicap::new(8., 15),
+            ai: AiPersonality::default(),
+            bonuses: BonusType::ALL.to_vec(),
+            bonus_weights: default_bonus_weights(),
+            training: Training::from(Drill::HighSpeed),
+        };
+
+        match preset {
+            RulesPreset::Classic => classic,
+            RulesPreset::Chaos => MatchRules {
+                name: String::from("Chaos"),
+                ball_speed: 14.,
+                ball_max_speed: 140.,
+                ball_touch_paddle_speed_up: 0.06,
+                ball_touch_edge_speed_up: 0.03,
+                paddle_speed: 14.,
+                bonus_min_delay: 3.,
+                bonus_max_delay: 12.,
+                max_bonuses: 8,
+                arena: String::from("Mixed"),
+                player: Handicap::new(4., 10),
+                computer: Handicap::new(6., 10),
+                ..classic
+            },
+            RulesPreset::Marathon => MatchRules {
+                name: String::from("Marathon"),
+                rounds_to_win: 3,
+                ball_speed: 8.,
+                ball_max_speed: 60.,
+                ball_touch_paddle_speed_up: 0.0125,
+                ball_touch_edge_speed_up: 0.005,
+                player: Handicap::new(5., 30),
+                computer: Handicap::new(8., 30),
+                ..classic
+            },
+            RulesPreset::Survival => MatchRules {
+                name: String::from("Survival"),
+                mode: GameMode::Survival,
+                bonus_min_delay: 5.,
+                bonus_max_delay: 20.,
+                player: Handicap::new(5., 10),
+                ..classic
+            },
+        }
+    }
+}
+
+/// The usual bonuses have a weight of one, the ones changing the game the most come less often.
+fn default_bonus_weights() -> HashMap<BonusType, f32> {
+    HashMap::from([
+        (BonusType::SplitBall, 1.5),
+        (BonusType::BallSpeedInArea, 1.),
+        (BonusType::BallsVerticalGravity, 1.),
+        (BonusType::ShrinkPaddleSize, 1.),
+        (BonusType::IncreasePaddleSize, 1.),
+        (BonusType::Shield, 0.8),
+        (BonusType::Magnet, 0.8),
+        (BonusType::GhostBall, 0.6),
+        // The four changes of the balls share the weight of a single bonus
+        (BonusType::BigBalls, 0.25),
+        (BonusType::SmallBalls, 0.25),
+        (BonusType::HeavyBalls, 0.25),
+        (BonusType::LightBalls, 0.25),
+        (BonusType::ReverseControls, 0.6),
+        (BonusType::FreezePaddles, 0.7),
+        (BonusType::SlowMotion, 0.7),
+        (BonusType::Portals, 0.6),
+        (BonusType::ExtraLife, 0.5),
+        (BonusType::StealHealth, 0.4),
+    ])
+}
+
+impl Default for MatchRules {
+    fn default() -> MatchRules {
+        MatchRules::from(RulesPreset::Classic)
+    }
+}
+
+/// Rejects a value out of its range, NaN included.
+fn check_range(name: &str, value: f32, min: f32, max: f32) -> Result<(), String> {
+    if !(min..=max).contains(&value) {
+        return Err(format!("{} must be between {} and {}", name, min, max));
+    }
+    Ok(())
+}
+
+impl MatchRules {
+    pub fn load(path: impl AsRef<Path>) -> Result<MatchRules, RonFileError> {
+        let content = fs::read_to_string(path)?;
+        let rules: MatchRules = ron::from_str(&content)?;
+        rules.validate().map_err(RonFileError::Invalid)?;
+        Ok(rules)
+    }
+
+    /// Rejects the values a hand-edited file may hold that the game cannot play with.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.rounds_to_win < 1 {
+            return Err(String::from("rounds_to_win must be at least 1"));
+        }
+        let delays = [self.bonus_min_delay, self.bonus_max_delay];
+        if delays.iter().any(|delay| !delay.is_finite() || *delay < 0.) {
+            return Err(String::from("the bonus delays must be positive"));
+        }
+        if self.bonus_min_delay > self.bonus_max_delay {
+            return Err(String::from("bonus_min_delay must not exceed bonus_max_delay"));
+        }
+        if self.bonus_weights.values().any(|weight| !weight.is_finite() || *weight < 0.) {
+            return Err(String::from("the bonus weights must be positive"));
+        }
+        // The same ranges as the fields of the custom match screen
+        check_range("ball_max_speed", self.ball_max_speed, 2., 300.)?;
+        check_range("ball_speed", self.ball_speed, 2., self.ball_max_speed)?;
+        check_range("ball_touch_paddle_speed_up", self.ball_touch_paddle_speed_up, 0., 0.2)?;
+        check_range("ball_touch_edge_speed_up", self.ball_touch_edge_speed_up, 0., 0.1)?;
+        check_range("paddle_speed", self.paddle_speed, 2., 40.)?;
+        check_range("the training speed", self.training.speed, 2., self.ball_max_speed)?;
+        check_range("the training spread", self.training.spread, 0., 120.)?;
+        check_range("the training interval", self.training.interval, 0.5, 10.)?;
+        self.player.validate()?;
+        self.computer.validate()
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), RonFileError> {
+        let content = ron::ser::to_string_pretty(self, Default::default())?;
+        Ok(fs::write(path, content)?)
+    }
+
+    /// The handicap of a player, all the computers share the same one.
+    pub fn handicap(&self, player: PlayerId) -> &Handicap {
+        if player.is_human() {
+            &self.player
+        } else {
+            &self.computer
+        }
+    }
+
+    pub fn handicap_mut(&mut self, player: PlayerId) -> &mut Handicap {
+        if player.is_human() {
+            &mut self.player
+        } else {
+            &mut self.computer
+        }
+    }
+
+    /// Whether the player takes part in the match with its paddles, goal and lifebar.
+    pub fn plays(&self, player: PlayerId) -> bool {
+        player.is_human() || self.mode.has_opponents()
+    }
+
+    /// Whether a second human player needs the arrows, the first one then plays with W and S.
+    pub fn has_second_human(&self) -> bool {
+        self.human_opponent || (self.human_teammate && self.team_layout != TeamLayout::Single)
+    }
+
+    /// How often the bonus spawns compared to the others.
+    pub fn bonus_weight(&self, bonus: BonusType) -> f32 {
+        self.bonus_weights.get(&bonus).copied().unwrap_or(1.)
+    }
+
+    /// The speed of the paddle once its handicap is applied.
+    pub fn paddle_speed(&self, player: PlayerId) -> f32 {
+        self.paddle_speed * self.handicap(player).paddle_speed
+    }
+
+    /// The number of rounds a match between these players can last at most.
+    pub fn max_rounds(&self, players: usize) -> usize {
+        self.rounds_to_win.saturating_sub(1) * players + 1
+    }
+}
+
+/// An error while reading or writing one of the RON files of the game.
+#[derive(Debug)]
+pub enum RonFileError {
+    Io(io::Error),
+    Ron(ron::Error),
+    /// The file was read but holds values that cannot be used.
+    Invalid(String),
+}
+
+impl fmt::Display for RonFileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RonFileError::Io(e) => write!(f, "{}", e),
+            RonFileError::Ron(e) => write!(f, "{}", e),
+            RonFileError::Invalid(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl From<io::Error> for RonFileError {
+    fn from(error: io::Error) -> RonFileError {
+        RonFileError::Io(error)
+    }
+}
+
+impl From<ron::Error> for RonFileError {
+    fn from(error: ron::Error) -> RonFileError {
+        RonFileError::Ron(error)
+    }
+}
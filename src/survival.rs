@@ -0,0 +1,186 @@
+use std::f32::consts::PI;
+use std::path::Path;
+use std::{cmp, fs};
+
+use bevy::prelude::*;
+use heron::prelude::*;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::game_collisions::{CollisionStatus, GameCollisionEvent};
+use crate::hud::update_text;
+use crate::rules::{GameMode, MatchRules, RonFileError};
+use crate::theme::Theme;
+use crate::{spawn_ball, Ball, BallAssets, GameScore, Paddle, PlayerId, States};
+
+/// The file in which the best survival runs are kept.
+pub const LEADERBOARD_FILE: &str = "leaderboard.ron";
+const LEADERBOARD_SIZE: usize = 10;
+
+/// The seconds between two waves, each wave adds a ball and speeds up the balls.
+const WAVE_DURATION: f32 = 20.;
+const WAVE_SPEED_UP: f32 = 0.1;
+
+/// The progress of the current survival run.
+#[derive(Default)]
+pub struct SurvivalRun {
+    pub elapsed: f32,
+    pub wave: usize,
+    /// The number of balls the player sent back.
+    pub returned: usize,
+}
+
+impl SurvivalRun {
+    /// Multiplies the speed of the balls and the frequency of the bonuses.
+    pub fn speed_multiplier(&self) -> f32 {
+        1. + WAVE_SPEED_UP * self.wave as f32
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SurvivalRecord {
+    pub time: f32,
+    pub returned: usize,
+}
+
+/// The best survival runs, the longest first.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Leaderboard {
+    pub records: Vec<SurvivalRecord>,
+    /// The run that just ended, to highlight it.
+    #[serde(skip)]
+    pub last: Option<SurvivalRecord>,
+}
+
+impl Leaderboard {
+    pub fn load(path: impl AsRef<Path>) -> Result<Leaderboard, RonFileError> {
+        let content = fs::read_to_string(path)?;
+        Ok(ron::from_str(&content)?)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), RonFileError> {
+        let content = ron::ser::to_string_pretty(self, Default::default())?;
+        Ok(fs::write(path, content)?)
+    }
+
+    pub fn record(&mut self, record: SurvivalRecord) {
+        self.records.push(record);
+        self.records.sort_by(|a, b| {
+            b.time
+                .partial_cmp(&a.time)
+                .unwrap_or(cmp::Ordering::Equal)
+                .then(b.returned.cmp(&a.returned))
+        });
+        self.records.truncate(LEADERBOARD_SIZE);
+        self.last = Some(record);
+    }
+}
+
+#[derive(Component, Default)]
+pub struct LeaderboardText;
+
+pub fn reset_survival_run(mut run: ResMut<SurvivalRun>) {
+    *run = SurvivalRun::default();
+}
+
+/// Starts a new wave every `WAVE_DURATION` seconds: the balls go faster and one more is thrown in.
+pub fn escalate_survival_waves(
+    mut commands: Commands,
+    time: Res<Time>,
+    rules: Res<MatchRules>,
+    mut run: ResMut<SurvivalRun>,
+    assets: Res<BallAssets>,
+    mut balls_query: Query<&mut Velocity, With<Ball>>,
+) {
+    if rules.mode != GameMode::Survival {
+        return;
+    }
+
+    run.elapsed += time.delta_seconds();
+    let wave = (run.elapsed / WAVE_DURATION) as usize;
+    if wave <= run.wave {
+        return;
+    }
+
+    let previous_multiplier = run.speed_multiplier();
+    run.wave = wave;
+    let multiplier = run.speed_multiplier();
+
+    for mut velocity in balls_query.iter_mut() {
+        velocity.linear *= multiplier / previous_multiplier;
+    }
+
+    let angle = rand::thread_rng().gen_range(0.0..2. * PI);
+    let velocity = Vec3::new(angle.cos(), angle.sin(), 0.) * rules.ball_speed * multiplier;
+    spawn_ball(&mut commands, &assets, Transform::default(), velocity);
+}
+
+pub fn count_survival_returns(
+    rules: Res<MatchRules>,
+    mut run: ResMut<SurvivalRun>,
+    mut collision_events: EventReader<GameCollisionEvent>,
+    paddles_query: Query<&PlayerId, With<Paddle>>,
+) {
+    use GameCollisionEvent::*;
+
+    if rules.mode != GameMode::Survival {
+        return;
+    }
+
+    for event in collision_events.iter() {
+        if let BallAndPaddle { status: CollisionStatus::Stopped, paddle, .. } = event {
+            if paddles_query.get(*paddle).is_ok_and(|p| p.is_human()) {
+                run.returned += 1;
+            }
+        }
+    }
+}
+
+/// Records the run in the leaderboard once the player has no health left.
+pub fn end_survival_run(
+    rules: Res<MatchRules>,
+    run: Res<SurvivalRun>,
+    score: Res<GameScore>,
+    mut leaderboard: ResMut<Leaderboard>,
+    mut state: ResMut<State<States>>,
+) {
+    if rules.mode != GameMode::Survival || score.player(PlayerId::HUMAN).health > 0 {
+        return;
+    }
+
+    leaderboard.record(SurvivalRecord { time: run.elapsed, returned: run.returned });
+    if let Err(e) = leaderboard.save(LEADERBOARD_FILE) {
+        warn!("Could not save the leaderboard: {}", e);
+    }
+
+    state.set(States::MatchSetup).unwrap();
+}
+
+/// Shows the leaderboard on the custom match screen when the survival mode is selected.
+pub fn display_leaderboard(
+    theme: Res<Theme>,
+    rules: Res<MatchRules>,
+    leaderboard: Res<Leaderboard>,
+    mut text_query: Query<&mut Text, With<LeaderboardText>>,
+) {
+    for mut text in text_query.iter_mut() {
+        let mut value = String::new();
+        if rules.mode == GameMode::Survival {
+            value.push_str("Survival leaderboard\n\n");
+            if let Some(last) = leaderboard.last {
+                value.push_str(&format!("Last run  {:>6.1}s  {:>4}\n\n", last.time, last.returned));
+            }
+            for (i, record) in leaderboard.records.iter().enumerate() {
+                value.push_str(&format!(
+                    "{:>2}.  {:>6.1}s  {:>4} returned\n",
+                    i + 1,
+                    record.time,
+                    record.returned
+                ));
+            }
+        }
+
+        update_text(&mut text, value, &theme);
+    }
+}
@@ -0,0 +1,238 @@
+use bevy::prelude::*;
+use bevy_kira_audio::Audio;
+use heron::prelude::*;
+
+use crate::arena::Arena;
+use crate::game_collisions::{CollisionStatus, GameCollisionEvent, GamePhysicsLayer};
+use crate::hud::update_text;
+use crate::obstacles::Obstacle;
+use crate::rules::{GameMode, MatchRules};
+use crate::theme::Theme;
+use crate::{AudioAssets, GameScore, Paddle, PlayerId, States};
+
+/// The seconds the player has to break every target.
+pub const TARGET_PRACTICE_DURATION: f32 = 90.;
+const TARGET_WIDTH: f32 = 0.5;
+const TARGET_HEIGHT: f32 = 1.;
+/// The distances of the columns of targets from the goals they protect.
+const TARGET_COLUMNS: [f32; 2] = [1.5, 2.5];
+const TARGET_SPACING: f32 = 1.2;
+
+/// A breakable target of the target practice, it is destroyed by the first ball touching it.
+#[derive(Component)]
+pub struct Target;
+
+/// The progress of the current target practice.
+#[derive(Default)]
+pub struct TargetPractice {
+    pub remaining: f32,
+    pub targets: usize,
+    pub broken: usize,
+    /// The number of balls the player sent toward the targets.
+    pub shots: usize,
+}
+
+impl TargetPractice {
+    /// The part of the shots that broke a target.
+    pub fn accuracy(&self) -> f32 {
+        if self.shots == 0 {
+            0.
+        } else {
+            (self.broken as f32 / self.shots as f32).min(1.)
+        }
+    }
+
+    /// Every target is worth its accuracy, the seconds left count when the wall is cleared.
+    pub fn score(&self) -> usize {
+        let targets = self.broken as f32 * 100. * self.accuracy();
+        let time = if self.broken == self.targets { self.remaining * 10. } else { 0. };
+        (targets + time).round() as usize
+    }
+}
+
+/// The outcome of the target practices played since the game started.
+#[derive(Default)]
+pub struct TargetPracticeResults {
+    pub last: Option<TargetPracticeResult>,
+    pub best: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct TargetPracticeResult {
+    pub broken: usize,
+    pub targets: usize,
+    pub accuracy: f32,
+    pub remaining: f32,
+    pub score: usize,
+}
+
+#[derive(Component, Default)]
+pub struct TargetPracticeText;
+
+/// Builds a wall of targets in front of the goals of the players who do not play.
+pub fn spawn_targets(
+    mut commands: Commands,
+    theme: Res<Theme>,
+    rules: Res<MatchRules>,
+    arena: Res<Arena>,
+    mut practice: ResMut<TargetPractice>,
+) {
+    *practice = TargetPractice { remaining: TARGET_PRACTICE_DURATION, ..Default::default() };
+    if rules.mode != GameMode::TargetPractice {
+        return;
+    }
+
+    for player in arena.player_ids().filter(|p| !rules.plays(*p)) {
+        let (goal, side) = (arena.player(player).goal, arena.player(player).side);
+        let inward = (-goal.position).normalize_or_zero();
+        let along = Vec2::new(-inward.y, inward.x);
+        let rotation = Quat::from_rotation_z(inward.y.atan2(inward.x));
+
+        for (column, distance) in TARGET_COLUMNS.iter().enumerate() {
+            // The columns are staggered so that no target hides behind another one
+            let offset = column as f32 * TARGET_SPACING / 2.;
+            let front = goal.position + inward * (goal.size.x / 2. + distance);
+            for step in -10..=10 {
+                let position = front + along * (step as f32 * TARGET_SPACING + offset);
+                if !side.contains(position) {
+                    continue;
+                }
+
+                commands
+                    .spawn_bundle(SpriteBundle {
+                        sprite: Sprite {
+                            color: theme.obstacles,
+                            custom_size: Some(Vec2::new(TARGET_WIDTH, TARGET_HEIGHT)),
+                            ..Default::default()
+                        },
+                        transform: Transform {
+                            translation: position.extend(0.),
+                            rotation,
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    })
+                    .insert(RigidBody::Static)
+                    .insert(CollisionShape::Cuboid {
+                        half_extends: Vec3::new(TARGET_WIDTH / 2., TARGET_HEIGHT / 2., 0.),
+                        border_radius: None,
+                    })
+                    .insert(PhysicMaterial {
+                        restitution: PhysicMaterial::PERFECTLY_ELASTIC_RESTITUTION,
+                        ..Default::default()
+                    })
+                    .insert(
+                        CollisionLayers::none()
+                            .with_group(GamePhysicsLayer::Target)
+                            .with_mask(GamePhysicsLayer::Ball),
+                    )
+                    .insert(Obstacle)
+                    .insert(Target);
+
+                practice.targets += 1;
+            }
+        }
+    }
+}
+
+/// Breaks the targets touched by the balls and counts the shots of the player.
+#[allow(clippy::too_many_arguments)]
+pub fn break_targets(
+    mut commands: Commands,
+    rules: Res<MatchRules>,
+    mut practice: ResMut<TargetPractice>,
+    mut collision_events: EventReader<GameCollisionEvent>,
+    paddles_query: Query<&PlayerId, With<Paddle>>,
+    targets_query: Query<(), With<Target>>,
+    audio_assets: Res<AudioAssets>,
+    audio: Res<Audio>,
+) {
+    use GameCollisionEvent::*;
+
+    if rules.mode != GameMode::TargetPractice {
+        return;
+    }
+
+    // Several balls may touch the same target at once
+    let mut broken = Vec::new();
+    for event in collision_events.iter() {
+        match event {
+            BallAndPaddle { status: CollisionStatus::Stopped, paddle, .. }
+                if paddles_query.get(*paddle).is_ok_and(|p| p.is_human()) =>
+            {
+                practice.shots += 1;
+            }
+            BallAndTarget { status: CollisionStatus::Started, target, .. }
+                if targets_query.get(*target).is_ok() && !broken.contains(target) =>
+            {
+                commands.entity(*target).despawn_recursive();
+                broken.push(*target);
+                practice.broken += 1;
+                audio.play(audio_assets.goal.clone());
+            }
+            _ => (),
+        }
+    }
+}
+
+/// Ends the practice when the wall is cleared, the time is up or the player has no health left.
+pub fn end_target_practice(
+    time: Res<Time>,
+    rules: Res<MatchRules>,
+    score: Res<GameScore>,
+    mut practice: ResMut<TargetPractice>,
+    mut results: ResMut<TargetPracticeResults>,
+    mut state: ResMut<State<States>>,
+) {
+    if rules.mode != GameMode::TargetPractice {
+        return;
+    }
+
+    practice.remaining = (practice.remaining - time.delta_seconds()).max(0.);
+    let cleared = practice.broken >= practice.targets;
+    if !cleared && practice.remaining > 0. && score.player(PlayerId::HUMAN).health > 0 {
+        return;
+    }
+
+    let result = TargetPracticeResult {
+        broken: practice.broken,
+        targets: practice.targets,
+        accuracy: practice.accuracy(),
+        remaining: practice.remaining,
+        score: practice.score(),
+    };
+    results.best = Some(results.best.map_or(result.score, |best| best.max(result.score)));
+    results.last = Some(result);
+
+    state.set(States::MatchSetup).unwrap();
+}
+
+/// Shows the last results on the custom match screen when the target practice is selected.
+pub fn display_target_practice_results(
+    theme: Res<Theme>,
+    rules: Res<MatchRules>,
+    results: Res<TargetPracticeResults>,
+    mut text_query: Query<&mut Text, With<TargetPracticeText>>,
+) {
+    for mut text in text_query.iter_mut() {
+        let mut value = String::new();
+        if rules.mode == GameMode::TargetPractice {
+            value.push_str(&format!("Target practice, {:.0}s\n\n", TARGET_PRACTICE_DURATION));
+            if let Some(last) = results.last {
+                value.push_str(&format!(
+                    "Last run  {}/{} targets\nAccuracy  {:.0}%\nTime left {:.1}s\nScore     {}\n\n",
+                    last.broken,
+                    last.targets,
+                    last.accuracy * 100.,
+                    last.remaining,
+                    last.score
+                ));
+            }
+            if let Some(best) = results.best {
+                value.push_str(&format!("Best score {}\n", best));
+            }
+        }
+
+        update_text(&mut text, value, &theme);
+    }
+}
@@ -0,0 +1,418 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::arena::Arena;
+use crate::campaign::{CampaignProgress, Unlock};
+use crate::obstacles::Obstacle;
+use crate::training::{BallMachine, BALL_MACHINE_PLAYER};
+use crate::{Ball, BonusType, FieldLine, Frozen, Ghost, Lifebar, Paddle, PlayerId, Round, States};
+
+/// The colors and cues used to draw the arena, the paddles and the balls.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Theme {
+    pub preset: ThemePreset,
+    pub background: Color,
+    pub field_lines: Color,
+    pub obstacles: Color,
+    /// The color of each player, the first one is the human player.
+    pub players: [Color; 4],
+    /// The pattern of each player, drawn when the shape cues are on.
+    pub cues: [ShapeCue; 4],
+    /// The tint of the bonuses waiting on the field.
+    pub bonuses: Color,
+    /// The color of the balls that no paddle touched yet.
+    pub neutral_ball: Color,
+    /// Tint the balls with the color of the last paddle that touched them.
+    pub tint_balls: bool,
+    /// Draw patterns on the paddles and the balls so that sides
+    /// can be told apart without relying on colors.
+    pub shape_cues: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ThemePreset {
+    Classic,
+    HighContrast,
+    Deuteranopia,
+    Protanopia,
+    Tritanopia,
+    Monochrome,
+    /// The cosmetic presets below are unlocked by the campaign.
+    Sunset,
+    Neon,
+    Phosphor,
+}
+
+impl ThemePreset {
+    pub const ALL: [ThemePreset; 9] = [
+        ThemePreset::Classic,
+        ThemePreset::HighContrast,
+        ThemePreset::Deuteranopia,
+        ThemePreset::Protanopia,
+        ThemePreset::Tritanopia,
+        ThemePreset::Monochrome,
+        ThemePreset::Sunset,
+        ThemePreset::Neon,
+        ThemePreset::Phosphor,
+    ];
+
+    pub fn next(&self) -> ThemePreset {
+        let index = ThemePreset::ALL.iter().position(|p| p == self).unwrap_or(0);
+        ThemePreset::ALL[(index + 1) % ThemePreset::ALL.len()]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ThemePreset::Classic => "Classic",
+            ThemePreset::HighContrast => "High contrast",
+            ThemePreset::Deuteranopia => "Deuteranopia",
+            ThemePreset::Protanopia => "Protanopia",
+            ThemePreset::Tritanopia => "Tritanopia",
+            ThemePreset::Monochrome => "Monochrome",
+            ThemePreset::Sunset => "Sunset",
+            ThemePreset::Neon => "Neon",
+            ThemePreset::Phosphor => "Phosphor",
+        }
+    }
+}
+
+impl From<ThemePreset> for Theme {
+    fn from(preset: ThemePreset) -> Theme {
+        let classic = Theme {
+            preset,
+            background: Color::rgb(0.239, 0.239, 0.239),
+            field_lines: Color::rgb(0.922, 0.922, 0.922),
+            obstacles: Color::rgb(0.55, 0.55, 0.55),
+            players: [
+                Color::rgb(0.706, 0.706, 1.),
+                Color::rgb(1., 0.706, 0.706),
+                Color::rgb(0.706, 1., 0.706),
+                Color::rgb(1., 0.95, 0.6),
+            ],
+            cues: [ShapeCue::Dots, ShapeCue::Stripe, ShapeCue::DoubleStripe, ShapeCue::Ladder],
+            bonuses: Color::WHITE,
+            neutral_ball: Color::WHITE,
+            tint_balls: true,
+            shape_cues: false,
+        };
+
+        // The colorblind-safe presets use the Okabe-Ito palette.
+        match preset {
+            ThemePreset::Classic => classic,
+            ThemePreset::HighContrast => Theme {
+                background: Color::BLACK,
+                field_lines: Color::WHITE,
+                obstacles: Color::rgb(0.75, 0.75, 0.75),
+                players: [
+                    Color::rgb(0.3, 0.6, 1.),
+                    Color::rgb(1., 0.85, 0.),
+                    Color::rgb(1., 0.4, 1.),
+                    Color::rgb(0.3, 1., 0.5),
+                ],
+                shape_cues: true,
+                ..classic
+            },
+            ThemePreset::Deuteranopia => Theme {
+                players: [
+                    Color::rgb(0., 0.447, 0.698),
+                    Color::rgb(0.902, 0.624, 0.),
+                    Color::rgb(0.941, 0.894, 0.259),
+                    Color::rgb(0.8, 0.475, 0.655),
+                ],
+                shape_cues: true,
+                ..classic
+            },
+            // The reds look dark without the long cones, the sides differ by their lightness
+            ThemePreset::Protanopia => Theme {
+                players: [
+                    Color::rgb(0.337, 0.706, 0.914),
+                    Color::rgb(0.941, 0.894, 0.259),
+                    Color::rgb(0., 0.620, 0.451),
+                    Color::rgb(0.8, 0.475, 0.655),
+                ],
+                obstacles: Color::rgb(0.45, 0.45, 0.5),
+                shape_cues: true,
+                ..classic
+            },
+            ThemePreset::Tritanopia => Theme {
+                players: [
+                    Color::rgb(0., 0.620, 0.451),
+                    Color::rgb(0.835, 0.369, 0.),
+                    Color::rgb(0.8, 0.475, 0.655),
+                    Color::rgb(0.941, 0.894, 0.259),
+                ],
+                shape_cues: true,
+                ..classic
+            },
+            ThemePreset::Monochrome => Theme {
+                players: [
+                    Color::rgb(0.922, 0.922, 0.922),
+                    Color::rgb(0.55, 0.55, 0.55),
+                    Color::rgb(0.75, 0.75, 0.75),
+                    Color::rgb(0.35, 0.35, 0.35),
+                ],
+                obstacles: Color::rgb(0.4, 0.4, 0.4),
+                tint_balls: false,
+                shape_cues: true,
+                ..classic
+            },
+            ThemePreset::Sunset => Theme {
+                background: Color::rgb(0.22, 0.12, 0.2),
+                field_lines: Color::rgb(1., 0.85, 0.7),
+                obstacles: Color::rgb(0.6, 0.35, 0.4),
+                players: [
+                    Color::rgb(1., 0.75, 0.3),
+                    Color::rgb(0.9, 0.3, 0.45),
+                    Color::rgb(0.6, 0.45, 0.9),
+                    Color::rgb(0.4, 0.8, 0.8),
+                ],
+                bonuses: Color::rgb(1., 0.88, 0.78),
+                ..classic
+            },
+            ThemePreset::Neon => Theme {
+                background: Color::rgb(0.04, 0.02, 0.1),
+                field_lines: Color::rgb(0.2, 1., 0.9),
+                obstacles: Color::rgb(0.5, 0.2, 0.8),
+                players: [
+                    Color::rgb(0.2, 1., 0.4),
+                    Color::rgb(1., 0.2, 0.8),
+                    Color::rgb(1., 0.9, 0.1),
+                    Color::rgb(0.3, 0.6, 1.),
+                ],
+                bonuses: Color::rgb(0.85, 1., 1.),
+                ..classic
+            },
+            ThemePreset::Phosphor => Theme {
+                background: Color::rgb(0.02, 0.08, 0.03),
+                field_lines: Color::rgb(0.3, 1., 0.4),
+                obstacles: Color::rgb(0.1, 0.45, 0.15),
+                players: [
+                    Color::rgb(0.5, 1., 0.55),
+                    Color::rgb(0.2, 0.7, 0.25),
+                    Color::rgb(0.75, 1., 0.3),
+                    Color::rgb(0.35, 0.85, 0.6),
+                ],
+                bonuses: Color::rgb(0.6, 1., 0.65),
+                neutral_ball: Color::rgb(0.7, 1., 0.7),
+                shape_cues: true,
+                ..classic
+            },
+        }
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Theme {
+        Theme::from(ThemePreset::Classic)
+    }
+}
+
+impl Theme {
+    /// The color of a player, the arenas with more players than colors reuse them.
+    pub fn paddle_color(&self, player: PlayerId) -> Color {
+        self.players[player.0 % self.players.len()]
+    }
+
+    pub fn cue(&self, player: PlayerId) -> ShapeCue {
+        self.cues[player.0 % self.cues.len()]
+    }
+
+    pub fn ball_color(&self, last_touched: Option<PlayerId>) -> Color {
+        match last_touched {
+            Some(player) if self.tint_balls => self.paddle_color(player),
+            _ => self.neutral_ball,
+        }
+    }
+}
+
+/// The patterns telling the players apart without relying on colors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShapeCue {
+    /// Dots on the paddle, a square on the balls.
+    Dots,
+    /// A stripe along the paddle, a diamond on the balls.
+    Stripe,
+    /// Two stripes along the paddle, a bar along the field on the balls.
+    DoubleStripe,
+    /// Rungs across the paddle, a bar across the field on the balls.
+    Ladder,
+}
+
+impl ShapeCue {
+    /// The size and the position of the marks drawn on a paddle.
+    fn paddle_marks(&self) -> Vec<(Vec2, Vec2)> {
+        match self {
+            ShapeCue::Dots => {
+                [-1., 0., 1.].map(|y| (Vec2::new(0.2, 0.2), Vec2::new(0., y))).to_vec()
+            }
+            ShapeCue::Stripe => vec![(Vec2::new(0.1, 3.), Vec2::ZERO)],
+            ShapeCue::DoubleStripe => {
+                [-0.1, 0.1].map(|x| (Vec2::new(0.06, 3.), Vec2::new(x, 0.))).to_vec()
+            }
+            ShapeCue::Ladder => {
+                [-1., -0.5, 0., 0.5, 1.].map(|y| (Vec2::new(0.3, 0.08), Vec2::new(0., y))).to_vec()
+            }
+        }
+    }
+
+    /// The size and the rotation of the mark drawn on a ball.
+    fn ball_mark(&self) -> (Vec2, f32) {
+        match self {
+            ShapeCue::Dots => (Vec2::new(0.15, 0.15), 0.),
+            ShapeCue::Stripe => (Vec2::new(0.15, 0.15), std::f32::consts::FRAC_PI_4),
+            ShapeCue::DoubleStripe => (Vec2::new(0.24, 0.08), 0.),
+            ShapeCue::Ladder => (Vec2::new(0.08, 0.24), 0.),
+        }
+    }
+}
+
+/// The pattern drawn on top of a paddle, the cue of its player.
+#[derive(Component)]
+pub struct PaddleCue;
+
+/// The mark drawn on top of a ball, the cue of the player that touched it last.
+#[derive(Component)]
+pub struct BallCue;
+
+pub fn spawn_paddle_cues(parent: &mut ChildBuilder, player: PlayerId, theme: &Theme) {
+    for (size, position) in theme.cue(player).paddle_marks() {
+        parent
+            .spawn_bundle(SpriteBundle {
+                sprite: Sprite {
+                    color: theme.background,
+                    custom_size: Some(size),
+                    ..Default::default()
+                },
+                transform: Transform::from_translation(position.extend(0.1)),
+                visibility: Visibility { is_visible: theme.shape_cues },
+                ..Default::default()
+            })
+            .insert(PaddleCue);
+    }
+}
+
+pub fn spawn_ball_cue(parent: &mut ChildBuilder) {
+    parent
+        .spawn_bundle(SpriteBundle {
+            sprite: Sprite { custom_size: Some(Vec2::new(0.15, 0.15)), ..Default::default() },
+            transform: Transform::from_translation(Vec3::new(0., 0., 0.1)),
+            visibility: Visibility { is_visible: false },
+            ..Default::default()
+        })
+        .insert(BallCue);
+}
+
+pub fn switch_theme(
+    mut keys: ResMut<Input<KeyCode>>,
+    mut theme: ResMut<Theme>,
+    progress: Res<CampaignProgress>,
+    state: Res<State<States>>,
+) {
+    // The T key types a letter on the tournament screen
+    if *state.current() != States::Tournament && keys.clear_just_pressed(KeyCode::T) {
+        // The classic preset is never locked
+        let mut preset = theme.preset.next();
+        while !progress.is_unlocked(&Unlock::Theme(preset)) {
+            preset = preset.next();
+        }
+        *theme = Theme::from(preset);
+        info!("Switched to the {} theme", theme.preset.name());
+    }
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn apply_theme(
+    theme: Res<Theme>,
+    arena: Res<Arena>,
+    mut clear_color: ResMut<ClearColor>,
+    mut lines_query: Query<&mut Sprite, (With<FieldLine>, Without<Paddle>, Without<PaddleCue>)>,
+    // The frozen paddles keep their tint, they get their color back once they thaw
+    mut paddles_query: Query<
+        (&mut Sprite, &PlayerId),
+        (With<Paddle>, Without<Frozen>, Without<FieldLine>, Without<PaddleCue>),
+    >,
+    mut cues_query: Query<(&mut Sprite, &mut Visibility), With<PaddleCue>>,
+    mut obstacles_query: Query<
+        &mut Sprite,
+        (With<Obstacle>, Without<FieldLine>, Without<Paddle>, Without<PaddleCue>),
+    >,
+    mut machines_query: Query<
+        &mut Sprite,
+        (
+            With<BallMachine>,
+            Without<Obstacle>,
+            Without<FieldLine>,
+            Without<Paddle>,
+            Without<PaddleCue>,
+        ),
+    >,
+    mut lifebars_query: Query<(&mut TextureAtlasSprite, &PlayerId), With<Lifebar>>,
+    mut rounds_query: Query<(&mut TextureAtlasSprite, &Round), Without<Lifebar>>,
+    mut bonuses_query: Query<
+        &mut TextureAtlasSprite,
+        (With<BonusType>, Without<Lifebar>, Without<Round>),
+    >,
+) {
+    if !theme.is_changed() && !arena.is_changed() {
+        return;
+    }
+
+    clear_color.0 = arena.background.unwrap_or(theme.background);
+
+    for mut sprite in lines_query.iter_mut() {
+        sprite.color = theme.field_lines;
+    }
+
+    for (mut sprite, player) in paddles_query.iter_mut() {
+        sprite.color = theme.paddle_color(*player);
+    }
+
+    for mut sprite in obstacles_query.iter_mut() {
+        sprite.color = theme.obstacles;
+    }
+
+    for mut sprite in machines_query.iter_mut() {
+        sprite.color = theme.paddle_color(BALL_MACHINE_PLAYER);
+    }
+
+    for (mut sprite, mut visibility) in cues_query.iter_mut() {
+        sprite.color = theme.background;
+        visibility.is_visible = theme.shape_cues;
+    }
+
+    for (mut sprite, player) in lifebars_query.iter_mut() {
+        sprite.color = theme.paddle_color(*player);
+    }
+
+    for (mut sprite, round) in rounds_query.iter_mut() {
+        sprite.color = theme.paddle_color(round.player);
+    }
+
+    for mut sprite in bonuses_query.iter_mut() {
+        sprite.color = theme.bonuses;
+    }
+}
+
+pub fn display_balls_colors(
+    theme: Res<Theme>,
+    mut balls_query: Query<(&mut TextureAtlasSprite, &Ball, &Children, Option<&Ghost>)>,
+    mut cues_query: Query<(&mut Sprite, &mut Visibility, &mut Transform), With<BallCue>>,
+) {
+    for (mut texture_atlas_sprite, ball, children, ghost) in balls_query.iter_mut() {
+        let player = ball.last_touched_team;
+        let alpha = ghost.map_or(1., |ghost| ghost.alpha);
+        texture_atlas_sprite.color = *theme.ball_color(player).set_a(alpha);
+
+        for child in children.iter() {
+            if let Ok((mut sprite, mut visibility, mut transform)) = cues_query.get_mut(*child) {
+                sprite.color = *theme.background.clone().set_a(alpha);
+                visibility.is_visible = theme.shape_cues && player.is_some();
+                if let Some(player) = player {
+                    let (size, rotation) = theme.cue(player).ball_mark();
+                    sprite.custom_size = Some(size);
+                    transform.rotation = Quat::from_rotation_z(rotation);
+                }
+            }
+        }
+    }
+}
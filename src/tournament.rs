@@ -0,0 +1,413 @@
+use std::fs;
+use std::path::Path;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::arena::{find_arena, Arena};
+use crate::rules::{GameMode, MatchRules, RonFileError};
+use crate::theme::Theme;
+use crate::{ArenaAssets, FontAssets, GameScore, PlayerId, States};
+
+/// The file in which the bracket and the results of the tournament are saved.
+pub const TOURNAMENT_FILE: &str = "tournament.ron";
+const MAX_NAME_LENGTH: usize = 12;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Elimination {
+    #[default]
+    Single,
+    /// The entrants are only eliminated after their second loss.
+    Double,
+}
+
+impl Elimination {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Elimination::Single => "Single elimination",
+            Elimination::Double => "Double elimination",
+        }
+    }
+
+    fn max_losses(&self) -> usize {
+        match self {
+            Elimination::Single => 1,
+            Elimination::Double => 2,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Entrant {
+    pub name: String,
+    pub human: bool,
+    pub losses: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct BracketMatch {
+    pub round: usize,
+    /// The indices of the two entrants.
+    pub entrants: [usize; 2],
+    pub winner: Option<usize>,
+}
+
+/// The entrants of a tournament and the matches played or to play.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Bracket {
+    pub elimination: Elimination,
+    pub entrants: Vec<Entrant>,
+    pub matches: Vec<BracketMatch>,
+    pub rounds: usize,
+}
+
+impl Bracket {
+    pub fn new(elimination: Elimination, entrants: Vec<Entrant>) -> Bracket {
+        let mut bracket = Bracket { elimination, entrants, matches: Vec::new(), rounds: 0 };
+        bracket.schedule_round();
+        bracket
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), RonFileError> {
+        let content = ron::ser::to_string_pretty(self, Default::default())?;
+        Ok(fs::write(path, content)?)
+    }
+
+    fn is_alive(&self, entrant: usize) -> bool {
+        self.entrants[entrant].losses < self.elimination.max_losses()
+    }
+
+    pub fn champion(&self) -> Option<usize> {
+        let alive: Vec<_> = (0..self.entrants.len()).filter(|e| self.is_alive(*e)).collect();
+        match alive[..] {
+            [champion] => Some(champion),
+            _ => None,
+        }
+    }
+
+    pub fn next_match(&self) -> Option<usize> {
+        self.matches.iter().position(|m| m.winner.is_none())
+    }
+
+    /// Pairs the entrants that have the same number of losses, the winners bracket
+    /// and the losers bracket of a double elimination. The last ones of each bracket
+    /// meet in the final, that is played again if the winner of the winners bracket loses it.
+    fn schedule_round(&mut self) {
+        if self.champion().is_some() || self.next_match().is_some() {
+            return;
+        }
+
+        let pools: Vec<Vec<usize>> = (0..self.elimination.max_losses())
+            .map(|losses| {
+                (0..self.entrants.len()).filter(|e| self.entrants[*e].losses == losses).collect()
+            })
+            .collect();
+
+        self.rounds += 1;
+        let round = self.rounds;
+        if pools.iter().all(|pool| pool.len() <= 1) {
+            let finalists: Vec<_> = pools.into_iter().flatten().collect();
+            if let [a, b] = finalists[..] {
+                self.matches.push(BracketMatch { round, entrants: [a, b], winner: None });
+            }
+            return;
+        }
+
+        // An odd entrant out gets a bye for this round
+        for pool in pools {
+            for pair in pool.chunks_exact(2) {
+                self.matches.push(BracketMatch {
+                    round,
+                    entrants: [pair[0], pair[1]],
+                    winner: None,
+                });
+            }
+        }
+    }
+
+    pub fn record(&mut self, index: usize, winner: usize) {
+        let played = &mut self.matches[index];
+        played.winner = Some(winner);
+        let loser =
+            if played.entrants[0] == winner { played.entrants[1] } else { played.entrants[0] };
+        self.entrants[loser].losses += 1;
+        self.schedule_round();
+    }
+}
+
+/// The state of the tournament screen: the entrants being entered,
+/// then the bracket and the match being played.
+#[derive(Default)]
+pub struct Tournament {
+    name: String,
+    human: bool,
+    elimination: Elimination,
+    entrants: Vec<Entrant>,
+    bracket: Option<Bracket>,
+    /// The rules every match of the tournament is played with.
+    rules: MatchRules,
+    /// The match being played and the entrants on the side of each player.
+    playing: Option<(usize, [usize; 2])>,
+    message: String,
+    /// Whether the typed characters are names, the ones typed before the screen opened are not.
+    typing: bool,
+}
+
+#[derive(Component)]
+pub struct TournamentScreen;
+
+#[derive(Component)]
+pub struct TournamentText;
+
+pub fn spawn_tournament_screen(
+    mut commands: Commands,
+    assets: Res<FontAssets>,
+    theme: Res<Theme>,
+    mut tournament: ResMut<Tournament>,
+) {
+    tournament.typing = false;
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            color: Color::NONE.into(),
+            ..Default::default()
+        })
+        .insert(TournamentScreen)
+        .with_children(|parent| {
+            let style = TextStyle {
+                font: assets.monogram.clone(),
+                font_size: 24.,
+                color: theme.field_lines,
+            };
+
+            parent
+                .spawn_bundle(TextBundle {
+                    text: Text {
+                        sections: vec![TextSection { value: String::new(), style }],
+                        alignment: Default::default(),
+                    },
+                    ..Default::default()
+                })
+                .insert(TournamentText);
+        });
+}
+
+pub fn despawn_tournament_screen(
+    mut commands: Commands,
+    screen_query: Query<Entity, With<TournamentScreen>>,
+) {
+    for entity in screen_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+pub fn edit_tournament(
+    mut keys: ResMut<Input<KeyCode>>,
+    mut characters: EventReader<ReceivedCharacter>,
+    mut state: ResMut<State<States>>,
+    mut rules: ResMut<MatchRules>,
+    mut tournament: ResMut<Tournament>,
+    arena_assets: Res<ArenaAssets>,
+    arenas: Res<Assets<Arena>>,
+) {
+    if keys.clear_just_pressed(KeyCode::Escape) {
+        state.set(States::MatchSetup).unwrap();
+        return;
+    }
+
+    match &tournament.bracket {
+        None => {
+            let arena = find_arena(&arena_assets, &arenas, &rules.arena);
+            edit_entrants(&mut keys, &mut characters, &rules, arena, &mut tournament)
+        }
+        Some(bracket) if bracket.champion().is_some() => {
+            if keys.clear_just_pressed(KeyCode::Return) {
+                tournament.bracket = None;
+                tournament.message.clear();
+            }
+        }
+        Some(bracket) => {
+            if keys.clear_just_pressed(KeyCode::Return) {
+                if let Some(index) = bracket.next_match() {
+                    let [a, b] = bracket.matches[index].entrants;
+                    let (first, second) = (&bracket.entrants[a], &bracket.entrants[b]);
+                    // A human entrant takes the side of the player
+                    let sides = if !first.human && second.human { [b, a] } else { [a, b] };
+                    let (player, opponent) =
+                        (&bracket.entrants[sides[0]], &bracket.entrants[sides[1]]);
+
+                    *rules = MatchRules {
+                        name: format!("{} vs {}", player.name, opponent.name),
+                        mode: GameMode::Versus,
+                        human_opponent: opponent.human,
+                        computer_player: !player.human,
+                        ..tournament.rules.clone()
+                    };
+                    tournament.playing = Some((index, sides));
+                    state.set(States::InitGame).unwrap();
+                }
+            }
+        }
+    }
+}
+
+fn edit_entrants(
+    keys: &mut Input<KeyCode>,
+    characters: &mut EventReader<ReceivedCharacter>,
+    rules: &MatchRules,
+    arena: Option<&Arena>,
+    tournament: &mut Tournament,
+) {
+    if !tournament.typing {
+        characters.iter().for_each(drop);
+        tournament.typing = true;
+        return;
+    }
+
+    for event in characters.iter() {
+        if !event.char.is_control() && tournament.name.chars().count() < MAX_NAME_LENGTH {
+            tournament.name.push(event.char);
+        }
+    }
+
+    if keys.clear_just_pressed(KeyCode::Tab) {
+        tournament.human = !tournament.human;
+    } else if keys.clear_just_pressed(KeyCode::F1) {
+        tournament.elimination = match tournament.elimination {
+            Elimination::Single => Elimination::Double,
+            Elimination::Double => Elimination::Single,
+        };
+    } else if keys.clear_just_pressed(KeyCode::Back) {
+        if tournament.name.pop().is_none() {
+            tournament.entrants.pop();
+        }
+    } else if keys.clear_just_pressed(KeyCode::Return) {
+        let name = tournament.name.trim().to_string();
+        if !name.is_empty() {
+            let human = tournament.human;
+            tournament.entrants.push(Entrant { name, human, losses: 0 });
+            tournament.name.clear();
+        } else if arena.is_some_and(|arena| arena.players.len() != 2) {
+            // The matches of the bracket only have a winner and a loser
+            tournament.message = format!("The {} arena is not made for two players", rules.arena);
+        } else if tournament.entrants.len() >= 2 {
+            let bracket = Bracket::new(tournament.elimination, tournament.entrants.clone());
+            tournament.rules = rules.clone();
+            tournament.message = save_bracket(&bracket);
+            tournament.bracket = Some(bracket);
+        } else {
+            tournament.message = String::from("A tournament needs at least two entrants");
+        }
+    }
+}
+
+fn save_bracket(bracket: &Bracket) -> String {
+    match bracket.save(TOURNAMENT_FILE) {
+        Ok(()) => format!("Saved to {}", TOURNAMENT_FILE),
+        Err(e) => format!("Could not save: {}", e),
+    }
+}
+
+/// Records the winner of a tournament match and goes back to the bracket.
+pub fn finish_tournament_match(
+    rules: Res<MatchRules>,
+    score: Res<GameScore>,
+    mut tournament: ResMut<Tournament>,
+    mut state: ResMut<State<States>>,
+) {
+    let (index, sides) = match tournament.playing {
+        Some(playing) => playing,
+        None => return,
+    };
+    tournament.playing = None;
+
+    let winner = match score.winner(&rules) {
+        Some(PlayerId(side)) if side < sides.len() => sides[side],
+        _ => {
+            tournament.message = String::from("The match was not finished");
+            state.set(States::Tournament).unwrap();
+            return;
+        }
+    };
+
+    if let Some(bracket) = tournament.bracket.as_mut() {
+        bracket.record(index, winner);
+        let message = save_bracket(bracket);
+        tournament.message = message;
+    }
+
+    state.set(States::Tournament).unwrap();
+}
+
+pub fn display_tournament(
+    theme: Res<Theme>,
+    tournament: Res<Tournament>,
+    mut text_query: Query<&mut Text, With<TournamentText>>,
+    added_query: Query<(), Added<TournamentText>>,
+) {
+    let changed = tournament.is_changed() || theme.is_changed();
+    if !changed && added_query.is_empty() {
+        return;
+    }
+
+    for mut text in text_query.iter_mut() {
+        let style = text.sections[0].style.clone();
+        let highlight = TextStyle { color: theme.paddle_color(PlayerId::HUMAN), ..style.clone() };
+        let mut sections = Vec::new();
+        let mut push = |value: String, style: &TextStyle| {
+            sections.push(TextSection { value, style: style.clone() })
+        };
+
+        match &tournament.bracket {
+            None => {
+                push(format!("Tournament - {}\n\n", tournament.elimination.name()), &style);
+                for entrant in tournament.entrants.iter() {
+                    let kind = if entrant.human { "Human" } else { "Computer" };
+                    push(format!("  {:<14}{}\n", entrant.name, kind), &style);
+                }
+                let kind = if tournament.human { "Human" } else { "Computer" };
+                push(format!("> {:<14}{}\n", format!("{}_", tournament.name), kind), &highlight);
+                push(
+                    String::from(
+                        "\nType a name  Enter add it  Tab human/computer  F1 elimination\n\
+                         Backspace remove  Enter with no name start  Escape back\n",
+                    ),
+                    &style,
+                );
+            }
+            Some(bracket) => {
+                push(format!("Tournament - {}\n\n", bracket.elimination.name()), &style);
+                let next = bracket.next_match();
+                for (i, played) in bracket.matches.iter().enumerate() {
+                    let [a, b] = played.entrants;
+                    let (a, b) = (&bracket.entrants[a].name, &bracket.entrants[b].name);
+                    let result = match played.winner {
+                        Some(winner) => format!("{} wins", bracket.entrants[winner].name),
+                        None => String::new(),
+                    };
+                    let line = format!("R{} {:>12} vs {:<12} {}\n", played.round, a, b, result);
+                    push(line, if Some(i) == next { &highlight } else { &style });
+                }
+
+                let help = match bracket.champion() {
+                    Some(champion) => format!(
+                        "\n{} is the champion!\nEnter new tournament  Escape back\n",
+                        bracket.entrants[champion].name
+                    ),
+                    None => String::from("\nEnter play the next match  Escape back\n"),
+                };
+                push(help, &style);
+            }
+        }
+        push(tournament.message.clone(), &style);
+
+        text.sections = sections;
+    }
+}
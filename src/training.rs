@@ -0,0 +1,218 @@
+use bevy::prelude::*;
+use heron::prelude::*;
+use rand::Rng;
+
+use crate::arena::Arena;
+use crate::game_collisions::{CollisionStatus, GameCollisionEvent};
+use crate::hud::update_text;
+use crate::rules::{GameMode, MatchRules};
+use crate::theme::Theme;
+use crate::{spawn_ball, BallAssets, Goal, Paddle, PlayerId, States};
+
+/// The most balls the machine lets on the field, it waits for some to leave otherwise.
+const MAX_TRAINING_BALLS: usize = 8;
+
+/// The computer player the machine replaces, it takes its place and its color.
+pub const BALL_MACHINE_PLAYER: PlayerId = PlayerId(1);
+
+/// The machine firing the balls of the training mode, it stands in front of the goal of the computer.
+#[derive(Component)]
+pub struct BallMachine {
+    timer: Timer,
+}
+
+/// The statistics of the current training session.
+#[derive(Default, Clone, Copy)]
+pub struct TrainingStats {
+    pub fired: usize,
+    pub returned: usize,
+    pub missed: usize,
+}
+
+impl TrainingStats {
+    /// The part of the balls that reached the player which were returned.
+    pub fn return_rate(&self) -> f32 {
+        let reached = self.returned + self.missed;
+        if reached == 0 {
+            0.
+        } else {
+            self.returned as f32 / reached as f32
+        }
+    }
+}
+
+/// The statistics of the last training session, shown on the custom match screen.
+#[derive(Default)]
+pub struct TrainingResults {
+    pub last: Option<(String, TrainingStats)>,
+}
+
+#[derive(Component, Default)]
+pub struct TrainingText;
+
+/// Where the ball machine stands and where it fires the balls to.
+fn machine_aim(arena: &Arena) -> (Vec2, Vec2) {
+    let position = arena.player(BALL_MACHINE_PLAYER).paddle_start;
+    let target = arena.player(PlayerId::HUMAN).paddle_start;
+    (position, (target - position).normalize_or_zero())
+}
+
+/// The velocity of a ball fired in the direction, randomly deviated inside the cone of the drill.
+pub fn training_velocity(rules: &MatchRules, direction: Vec2) -> Vec3 {
+    let half_spread = rules.training.spread.to_radians() / 2.;
+    let angle = if half_spread > 0. {
+        rand::thread_rng().gen_range(-half_spread..=half_spread)
+    } else {
+        0.
+    };
+    Quat::from_rotation_z(angle).mul_vec3(direction.extend(0.)) * rules.training.speed
+}
+
+/// The velocity of the first ball of a training, served from the center toward the player.
+pub fn training_serve(rules: &MatchRules, arena: &Arena) -> Vec3 {
+    let target = arena.player(PlayerId::HUMAN).paddle_start;
+    training_velocity(rules, target.normalize_or_zero())
+}
+
+pub fn spawn_ball_machine(
+    mut commands: Commands,
+    theme: Res<Theme>,
+    rules: Res<MatchRules>,
+    arena: Res<Arena>,
+    mut stats: ResMut<TrainingStats>,
+) {
+    *stats = TrainingStats::default();
+    if rules.mode != GameMode::Training {
+        return;
+    }
+
+    let (position, direction) = machine_aim(&arena);
+    commands
+        .spawn_bundle(SpriteBundle {
+            sprite: Sprite {
+                color: theme.paddle_color(BALL_MACHINE_PLAYER),
+                custom_size: Some(Vec2::new(1.2, 0.8)),
+                ..Default::default()
+            },
+            transform: Transform {
+                translation: position.extend(0.),
+                rotation: Quat::from_rotation_z(direction.y.atan2(direction.x)),
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .insert(BallMachine { timer: Timer::from_seconds(rules.training.interval, true) });
+}
+
+/// Fires the balls of the drill at regular intervals.
+#[allow(clippy::too_many_arguments)]
+pub fn fire_ball_machine(
+    mut commands: Commands,
+    time: Res<Time>,
+    rules: Res<MatchRules>,
+    arena: Res<Arena>,
+    assets: Res<BallAssets>,
+    mut stats: ResMut<TrainingStats>,
+    mut machines_query: Query<&mut BallMachine>,
+    balls_query: Query<(), With<crate::Ball>>,
+) {
+    let (position, direction) = machine_aim(&arena);
+    let drill = rules.training.drill;
+
+    for mut machine in machines_query.iter_mut() {
+        if !machine.timer.tick(time.delta()).just_finished() {
+            continue;
+        }
+
+        if balls_query.iter().count() + drill.balls() > MAX_TRAINING_BALLS {
+            continue;
+        }
+
+        for _ in 0..drill.balls() {
+            let velocity = training_velocity(&rules, direction);
+            let transform = Transform::from_translation(position.extend(0.));
+            let ball = spawn_ball(&mut commands, &assets, transform, velocity);
+
+            // The gravity curves the ball toward the top or the bottom of the field
+            if drill.gravity() > 0. {
+                let sign = if rand::thread_rng().gen() { 1. } else { -1. };
+                let gravity = direction.perp() * sign * drill.gravity();
+                commands.entity(ball).insert(Acceleration::from_linear(gravity.extend(0.)));
+            }
+
+            stats.fired += 1;
+        }
+    }
+}
+
+/// Counts the balls returned or missed by the player.
+pub fn count_training_returns(
+    rules: Res<MatchRules>,
+    mut stats: ResMut<TrainingStats>,
+    mut collision_events: EventReader<GameCollisionEvent>,
+    paddles_query: Query<&PlayerId, With<Paddle>>,
+    goals_query: Query<&PlayerId, With<Goal>>,
+) {
+    use GameCollisionEvent::*;
+
+    if rules.mode != GameMode::Training {
+        return;
+    }
+
+    for event in collision_events.iter() {
+        match event {
+            BallAndPaddle { status: CollisionStatus::Stopped, paddle, .. }
+                if paddles_query.get(*paddle).is_ok_and(|p| p.is_human()) =>
+            {
+                stats.returned += 1;
+            }
+            BallAndGoal { status: CollisionStatus::Started, goal, .. }
+                if goals_query.get(*goal).is_ok_and(|p| p.is_human()) =>
+            {
+                stats.missed += 1;
+            }
+            _ => (),
+        }
+    }
+}
+
+/// Ends the training session with Escape, it never ends on its own.
+pub fn stop_training(
+    mut keys: ResMut<Input<KeyCode>>,
+    rules: Res<MatchRules>,
+    stats: Res<TrainingStats>,
+    mut results: ResMut<TrainingResults>,
+    mut state: ResMut<State<States>>,
+) {
+    if rules.mode == GameMode::Training && keys.clear_just_pressed(KeyCode::Escape) {
+        results.last = Some((rules.training.drill.name().to_string(), *stats));
+        state.set(States::MatchSetup).unwrap();
+    }
+}
+
+/// Shows the statistics of the last session on the custom match screen.
+pub fn display_training_results(
+    theme: Res<Theme>,
+    rules: Res<MatchRules>,
+    results: Res<TrainingResults>,
+    mut text_query: Query<&mut Text, With<TrainingText>>,
+) {
+    for mut text in text_query.iter_mut() {
+        let mut value = String::new();
+        if rules.mode == GameMode::Training {
+            value.push_str("Training, Escape ends a session\n\n");
+            if let Some((drill, stats)) = &results.last {
+                value.push_str(&format!(
+                    "Last session  {}\nFired     {}\nReturned  {}\nMissed    {}\nReturn rate {:.0}%\n",
+                    drill,
+                    stats.fired,
+                    stats.returned,
+                    stats.missed,
+                    stats.return_rate() * 100.
+                ));
+            }
+        }
+
+        update_text(&mut text, value, &theme);
+    }
+}